@@ -10,7 +10,7 @@ use serde_json::{
     from_str
 };
 
-use crate::struct_diff::{StructDiff, Structure};
+use crate::struct_diff::{Conflicts, Inflation, Resolution, Resolved, StructDiff, Structure};
 use crate::utils::*;
 
 //avoid typo errors
@@ -21,6 +21,36 @@ const PATCHED: &str = "patched_";
 const AMENDED: &str = "amended_";
 const UNREGISTERED: &str = "UNREGISTERED_";
 
+//  Diagnostics go to stderr so they don't mix with output an editor reads back
+fn report(map: &str, structure: &Structure) {
+    for (divider, diagnostic) in structure.diagnostics() {
+        eprintln!("warning: {} map, {}: {}", map, divider, diagnostic);
+    }
+
+    for error in structure.index().errors {
+        eprintln!("warning: {} map: {}", map, error);
+    }
+}
+
+//  Views show changed objects whole with everything outside the filter unless told otherwise
+fn parse_inflation(inflation: Option<&str>) -> AnyHow<Inflation> {
+    Ok(inflation
+        .map(from_str::<InflationDef>)
+        .transpose()?
+        .map_or_else(Inflation::default, Inflation::from))
+}
+
+//  Conflicts are left for the author unless told how to settle them
+fn parse_resolution(resolution: Option<&str>) -> AnyHow<Resolution> {
+    Ok(resolution
+        .map(from_str::<ResolutionDef>)
+        .transpose()?
+        .map(Resolution::try_from)
+        .transpose()
+        .context("Invalid resolution rule")?
+        .unwrap_or_default())
+}
+
 pub struct ProjectManager {
     main_dir: PathBuf,
 }
@@ -85,7 +115,8 @@ impl ProjectManager {
         Ok(())
     }
 
-    pub fn gen_mod(&self, map_id: &str, original: &str, temp: &str, comment: &str) -> AnyHow<()> {
+    //  With a config the mod is diffed by object spans instead of lines
+    pub fn gen_mod(&self, map_id: &str, original: &str, temp: &str, comment: &str, config: Option<&str>) -> AnyHow<()> {
         let source = self
             .main_dir
             .join(map_id)
@@ -101,11 +132,16 @@ impl ProjectManager {
         let new_mod_name = format!("{}{}", UNREGISTERED, self.max_mod_id(map_id)? + 1);
         
         let modded_diff = {
-            let struct_diff = StructDiff::build_from(
-                source.as_str(),
-                modded.as_str(),
-                &comment.replace(IO_SEPARATOR, "[sanetized]")
-            );
+            let comment = comment.replace(IO_SEPARATOR, "[sanetized]");
+            let struct_diff = match config {
+                Some(config) => StructDiff::build_from_spans(
+                    source.as_str(),
+                    modded.as_str(),
+                    &from_str::<ConfigDef>(config)?.into(),
+                    &comment
+                ),
+                None => StructDiff::build_from(source.as_str(), modded.as_str(), &comment),
+            };
             
             let def = StructDiffDef::from(struct_diff);
             to_string_pretty(&def)?
@@ -123,105 +159,186 @@ impl ProjectManager {
             .remove()
     }
 
-    pub fn view_mod(&self, map_id: &str, original: &str, mod_id: &str, config: &str) -> AnyHow<()> {
+    //  The amended version of a pending mod if it has been amended
+    fn selected_mod(&self, map_id: &str, mod_id: &str) -> String {
+        let amended = format!("{}{}", AMENDED, mod_id);
+
+        let hot = self
+            .main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(&amended)
+            .is_file();
+
+        if hot { amended } else { format!("{}{}", PENDING, mod_id) }
+    }
+
+    //  A mod from the map's mods along with the original cut into the same units
+    fn load_mod(&self, map_id: &str, original: &str, mod_file: &str, config: &str) -> AnyHow<(Structure, StructDiff)> {
         let source = self
             .main_dir
             .join(map_id)
             .join(original)
             .read()?;
-        
+
         let mod_file = self
             .main_dir
             .join(map_id)
             .join(MODS)
-            .join(mod_id)
+            .join(mod_file)
             .read()?;
 
+        let struct_diff: StructDiff = from_str::<StructDiffDef>(&mod_file)?.into();
+
         let structure = Structure {
-            contents: source.lines().map(|s| s.to_string()).collect(),
+            contents: struct_diff.units(&source),
             config: from_str::<ConfigDef>(config)?.into()
         };
 
-        let struct_diff: StructDiff = from_str::<StructDiffDef>(&mod_file)?.into();
-        
-        println!("{}", structure.forward_inflate(&struct_diff).contents.join("\n"));
+        Ok((structure, struct_diff))
+    }
+
+    //  Same as load_mod with the super mod between the two, which the mod has to have been
+    //  diffed the same way as for their units to line up
+    fn load_folding(&self, map_id: &str, original: &str, mod_file: &str, config: &str) -> AnyHow<(Structure, StructDiff, StructDiff)> {
+        let (original, modded) = self.load_mod(map_id, original, mod_file, config)?;
+
+        let super_mod = self
+            .main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(SUPER_MOD)
+            .read()?;
+
+        let super_mod: StructDiff = from_str::<StructDiffDef>(&super_mod)?.into();
+
+        if super_mod.spans != modded.spans {
+            bail!("Mod was not diffed the same way as the mods already folded");
+        }
+
+        Ok((original, super_mod, modded))
+    }
+
+    pub fn view_mod(&self, map_id: &str, original: &str, mod_id: &str, config: &str, inflation: Option<&str>) -> AnyHow<()> {
+        let (structure, struct_diff) = self.load_mod(map_id, original, mod_id, config)?;
+
+        report("original", &structure);
+        report("modded", &structure.patched(&struct_diff));
+
+        let inflated = structure.forward_inflate(&struct_diff, &parse_inflation(inflation)?);
+        for object in inflated.describe() {
+            eprintln!("shown: {}", object);
+        }
+
+        println!("{}", struct_diff.join(&inflated.structure.contents));
         Ok(())
     }
 
-    pub fn try_fold(&self, map_id: &str, original: &str, mod_id: &str, config: &str) -> AnyHow<()> {
-        if !self.main_dir.join(MODS).join(SUPER_MOD).is_file() {
-            let modded = self
-                .main_dir
-                .join(map_id)
-                .join(MODS)
-                .join(format!("{}{}", PENDING, mod_id).as_str());
+    //  Where each line view-mod prints came from, in the same order
+    pub fn trace_mod(&self, map_id: &str, original: &str, mod_id: &str, config: &str, inflation: Option<&str>) -> AnyHow<()> {
+        let (structure, struct_diff) = self.load_mod(map_id, original, mod_id, config)?;
 
-            let super_mod = self
-                .main_dir
-                .join(map_id)
-                .join(MODS)
-                .join(SUPER_MOD);
+        let lines = structure
+            .forward_inflate(&struct_diff, &parse_inflation(inflation)?)
+            .lines
+            .into_iter()
+            .map(ProvenanceDef::from)
+            .collect::<Vec<_>>();
 
-            let patched = self
-                .main_dir
-                .join(map_id)
-                .join(MODS)
-                .join(format!("{}{}", PATCHED, mod_id).as_str());
+        println!("{}", to_string_pretty(&lines)?);
+        Ok(())
+    }
 
-            super_mod.copy_from(&modded)?;
-            patched.copy_from(&modded)?;
-            Ok(())
+    pub fn summarize_mod(&self, map_id: &str, original: &str, mod_id: &str, config: &str) -> AnyHow<()> {
+        let (structure, struct_diff) = self.load_mod(map_id, original, mod_id, config)?;
+
+        report("original", &structure);
+
+        for change in structure.summary(&struct_diff) {
+            println!("{}", change);
         }
-        else {
-            let mod_name = {
-                let pending = format!("{}{}", PENDING, mod_id);
-                let amended = format!("{}{}", AMENDED, mod_id);
+        Ok(())
+    }
 
-                let hot = self
-                    .main_dir
-                    .join(MODS)
-                    .join(&amended)
-                    .is_file();
+    //  Paths to every object the mod changes so editors can go straight to them
+    pub fn locate_mod(&self, map_id: &str, original: &str, mod_id: &str, config: &str) -> AnyHow<()> {
+        let (structure, struct_diff) = self.load_mod(map_id, original, mod_id, config)?;
 
-                if hot { amended } else { pending }
-            };
-       
-            let original = self
-                .main_dir
-                .join(map_id)
-                .join(original)
-                .read()?;
+        report("original", &structure);
 
-            let super_mod = self
-                .main_dir
+        let breadcrumbs = structure
+            .breadcrumbs(&struct_diff)
+            .into_iter()
+            .map(BreadcrumbDef::from)
+            .collect::<Vec<_>>();
+
+        println!("{}", to_string_pretty(&breadcrumbs)?);
+        Ok(())
+    }
+
+    pub fn try_fold(&self, map_id: &str, original: &str, mod_id: &str, config: &str, resolution: Option<&str>, inflation: Option<&str>) -> AnyHow<()> {
+        let mod_name = self.selected_mod(map_id, mod_id);
+        let modded_path = self
+            .main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(mod_name.as_str());
+
+        if !self.main_dir.join(map_id).join(MODS).join(SUPER_MOD).is_file() {
+            //  The first mod folded is the super mod as it is, apart from what it's called
+            let mut super_mod: StructDiff = from_str::<StructDiffDef>(&modded_path.read()?)?.into();
+            super_mod.comment = "Super Mod".to_string();
+
+            self.main_dir
                 .join(map_id)
                 .join(MODS)
                 .join(SUPER_MOD)
-                .read()?;
+                .write_plus(&to_string(&StructDiffDef::from(super_mod))?)?;
 
-            let modded_path = self
-                .main_dir
+            self.main_dir
                 .join(map_id)
                 .join(MODS)
-                .join(mod_name.as_str());
+                .join(format!("{}{}", PATCHED, mod_id).as_str())
+                .copy_from(&modded_path)?;
 
-            let original = Structure {
-                contents: original.lines().map(|s| s.to_string()).collect(),
-                config: from_str::<ConfigDef>(config)?.into()
-            };
+            Ok(())
+        }
+        else {
+            let (original, mut super_mod, modded) = self.load_folding(map_id, original, &mod_name, config)?;
+
+            report("original", &original);
+            report("folded", &original.patched(&super_mod));
+            report("modded", &original.patched(&modded));
 
-            let mut super_mod: StructDiff = from_str::<StructDiffDef>(&super_mod)?.into();
-            let modded: StructDiff = from_str::<StructDiffDef>(&modded_path.read()?)?.into();
+            let resolution = parse_resolution(resolution)?;
+
+            if let Some(Conflicts { left, right, .. }) = original.conflicts(&super_mod, &modded, &resolution, &parse_inflation(inflation)?) {
+                for (side, conflicts) in [("folded", &left), ("modded", &right)] {
+                    for object in conflicts.describe() {
+                        eprintln!("conflict: {} {}", side, object);
+                    }
+                }
 
-            if let Some((conflicts_0, conflicts_1)) = original.conflicts(&super_mod, &modded) {
                 println!("{}\n{}\n{}",
-                    conflicts_0.contents.join("\n"),
+                    super_mod.join(&left.structure.contents),
                     IO_SEPARATOR,
-                    conflicts_1.contents.join("\n")
+                    modded.join(&right.structure.contents)
                 )
             }
             else {
-                super_mod.extend(modded);
+                //  The mod is replayed on top of the super mod unless the merge had to settle
+                //  objects or make an edit both made once, where replaying would disagree with it
+                let merged = original.merge(&super_mod, &modded, &resolution);
+                let mut extended = super_mod.clone();
+                extended.extend(modded.clone(), &original.contents);
+
+                super_mod = match merged.resolved.is_empty() && extended.patch_units(&original.contents) == merged.units {
+                    true => extended,
+                    false => super_mod.rebuilt(&original.contents, &merged.units, "Super Mod"),
+                };
+                super_mod.resolved.extend(merged.resolved
+                    .into_iter()
+                    .map(|resolved| Resolved { mod_id: mod_id.to_string(), ..resolved }));
                 let contents = to_string(&StructDiffDef::from(super_mod))?;
 
                 self.main_dir
@@ -241,10 +358,74 @@ impl ProjectManager {
         }
     }
 
-    pub fn amend_mod(&self, map_id: &str, original: &str, mod_id: &str, comment: &str) -> AnyHow<()> {
+    //  Takes a folded mod back out of the super mod and makes it pending again
+    pub fn unfold(&self, map_id: &str, original: &str, mod_id: &str, config: &str) -> AnyHow<()> {
+        let patched = format!("{}{}", PATCHED, mod_id);
+        let patched_path = self
+            .main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(patched.as_str());
+
+        if !patched_path.is_file() {
+            bail!("Mod hasn't been folded");
+        }
+
+        //  Skipped mods leave an empty patch and nothing to take out of the super mod
+        if patched_path.read()?.is_empty() {
+            return patched_path.remove();
+        }
+
+        let (original, super_mod, folded) = self.load_folding(map_id, original, &patched, config)?;
+
+        let (unfolded, overwritten) = super_mod.unfold(&folded, mod_id, &original.contents);
+        for object in original.patched(&folded).describe(&overwritten) {
+            eprintln!("warning: other folded mods changed the {} this mod added, their changes are kept", object);
+        }
+
+        self.main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(SUPER_MOD)
+            .write_plus(&to_string(&StructDiffDef::from(unfolded))?)?;
+
+        patched_path.remove()
+    }
+
+    //  Prints the original with both the folded mods and this one applied, conflicts and all
+    pub fn merge_mod(&self, map_id: &str, original: &str, mod_id: &str, config: &str, resolution: Option<&str>) -> AnyHow<()> {
+        let (original, super_mod, modded) = self.load_folding(map_id, original, &self.selected_mod(map_id, mod_id), config)?;
+
+        report("original", &original);
+
+        let merged = original.merge(&super_mod, &modded, &parse_resolution(resolution)?);
+        if !merged.conflicts.is_empty() {
+            eprintln!("{} conflicting objects marked", merged.conflicts.len());
+        }
+
+        println!("{}", super_mod.join(&merged.units));
+        Ok(())
+    }
+
+    //  Conflicts between the folded mods and the mod try-fold would fold as JSON, empty if it folds cleanly
+    pub fn report_conflicts(&self, map_id: &str, original: &str, mod_id: &str, config: &str, resolution: Option<&str>, inflation: Option<&str>) -> AnyHow<()> {
+        let (original, super_mod, modded) = self.load_folding(map_id, original, &self.selected_mod(map_id, mod_id), config)?;
+
+        let resolution = parse_resolution(resolution)?;
+
+        let conflicts = original
+            .conflicts(&super_mod, &modded, &resolution, &parse_inflation(inflation)?)
+            .map_or_else(Vec::new, |conflicts| conflicts.objects);
+
+        println!("{}", to_string_pretty(&ReportDef::from(conflicts))?);
+        Ok(())
+    }
+
+    //  Mods diffed by span have to be amended the same way, which needs the config
+    pub fn amend_mod(&self, map_id: &str, original: &str, mod_id: &str, comment: &str, config: Option<&str>) -> AnyHow<()> {
         let stdin = io::stdin();
         let mut stdin = stdin.lock();
-        let new_contents = stdin.fill_buf()?;
+        let new_contents = std::str::from_utf8(stdin.fill_buf()?)?;
 
         let original = self
             .main_dir
@@ -252,11 +433,67 @@ impl ProjectManager {
             .join(original)
             .read()?;
 
-        let modded = StructDiff::build_from(
-            original.as_str(),
-            std::str::from_utf8(new_contents)?,
+        let pending = self
+            .main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(format!("{}{}", PENDING, mod_id).as_str())
+            .read()?;
+
+        let pending: StructDiff = from_str::<StructDiffDef>(&pending)?.into();
+
+        let comment = comment.replace(IO_SEPARATOR, "[sanetized]");
+        let modded = match (&pending.spans, config) {
+            (Some(_), Some(config)) => StructDiff::build_from_spans(
+                original.as_str(),
+                new_contents,
+                &from_str::<ConfigDef>(config)?.into(),
+                &comment
+            ),
+            (Some(_), None) => bail!("Mod was diffed by span, amending it needs the config"),
+            (None, _) => StructDiff::build_from(original.as_str(), new_contents, &comment),
+        };
+
+        self.main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(format!("{}{}", AMENDED, mod_id).as_str())
+            .write_plus(&to_string(&StructDiffDef::from(modded))?)
+    }
+
+    //  A mod with the edits made to the view of it view-mod printed, read from stdin.
+    //  The view has to have been inflated the same way for the edits to line up.
+    fn edit_view(&self, map_id: &str, original: &str, mod_file: &str, comment: &str, config: &str, inflation: Option<&str>) -> AnyHow<StructDiff> {
+        let stdin = io::stdin();
+        let mut stdin = stdin.lock();
+        let edited = std::str::from_utf8(stdin.fill_buf()?)?;
+
+        let (structure, struct_diff) = self.load_mod(map_id, original, mod_file, config)?;
+
+        Ok(structure.edit_view(
+            &struct_diff,
+            &parse_inflation(inflation)?,
+            &struct_diff.units(edited),
             &comment.replace(IO_SEPARATOR, "[sanetized]")
-        );
+        )?)
+    }
+
+    //  Same as gen-mod but from an edited view of another mod rather than a whole map
+    pub fn gen_mod_from_view(&self, map_id: &str, original: &str, mod_id: &str, comment: &str, config: &str, inflation: Option<&str>) -> AnyHow<()> {
+        let modded = self.edit_view(map_id, original, mod_id, comment, config, inflation)?;
+        let new_mod_name = format!("{}{}", UNREGISTERED, self.max_mod_id(map_id)? + 1);
+
+        self.main_dir
+            .join(map_id)
+            .join(MODS)
+            .join(new_mod_name.as_str())
+            .write_plus(&to_string_pretty(&StructDiffDef::from(modded))?)
+    }
+
+    //  Same as amend-mod but from an edited view of the pending mod rather than a whole map
+    pub fn amend_mod_from_view(&self, map_id: &str, original: &str, mod_id: &str, comment: &str, config: &str, inflation: Option<&str>) -> AnyHow<()> {
+        let pending = format!("{}{}", PENDING, mod_id);
+        let modded = self.edit_view(map_id, original, &pending, comment, config, inflation)?;
 
         self.main_dir
             .join(map_id)
@@ -355,7 +592,7 @@ impl ProjectManager {
             .context("Failed to deserialize super mod")?
             .into();
 
-        let patched = super_mod.patch(source.lines()).join("\n");
+        let patched = super_mod.patch(&source);
         let temp_path = self
             .main_dir
             .join(map_id)
@@ -381,3 +618,48 @@ impl ProjectManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const ORIGINAL: &str = indoc!(
+        "objs: {
+            press: {
+                lane: 0,
+                time: 0,
+            },
+        }"
+    );
+
+    const CONFIG: &str = r#"{
+        "keys": [{ "fuzzed": "lane: [0-9]*", "strict": "[0-9]+" }],
+        "filter": { "prefix": "objs:", "open": "\\{", "close": "\\}" },
+        "expander": { "prefix": "press: \\{", "open": "\\{", "close": "\\}" }
+    }"#;
+
+    #[test]
+    fn fold_and_unfold_first_mod() {
+        let main_dir = std::env::temp_dir().join(format!("omfg-fold-{}", std::process::id()));
+        let project_manager = ProjectManager::new(main_dir.to_str().unwrap());
+        let mods = main_dir.join("map").join(MODS);
+
+        let modded = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("time: 0", "time: 4"), "first");
+        main_dir.join("map").join("original").write_plus(ORIGINAL).unwrap();
+        mods.join("pending_1").write_plus(&to_string(&StructDiffDef::from(modded)).unwrap()).unwrap();
+
+        project_manager.try_fold("map", "original", "1", CONFIG, None, None).unwrap();
+        let super_mod: StructDiff = from_str::<StructDiffDef>(&mods.join(SUPER_MOD).read().unwrap()).unwrap().into();
+        assert_eq!(super_mod.comment, "Super Mod");
+        assert_eq!(super_mod.patch(ORIGINAL), ORIGINAL.replace("time: 0", "time: 4"));
+        assert!(mods.join("patched_1").is_file());
+
+        project_manager.unfold("map", "original", "1", CONFIG).unwrap();
+        let super_mod: StructDiff = from_str::<StructDiffDef>(&mods.join(SUPER_MOD).read().unwrap()).unwrap().into();
+        assert_eq!(super_mod.patch(ORIGINAL), ORIGINAL);
+        assert!(!mods.join("patched_1").exists());
+
+        std::fs::remove_dir_all(main_dir).unwrap();
+    }
+}
@@ -13,7 +13,7 @@ use anyhow::{Context, anyhow, bail, Result as AnyHow};
 use mac_address::get_mac_address;
 use short_crypt::ShortCrypt;
 
-use crate::utils::{PathBufExt, DebugPrint};
+use crate::utils::PathBufExt;
 
 use super::*;
 
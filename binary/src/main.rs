@@ -10,6 +10,22 @@ use anyhow::{Context, anyhow};
 use cli::*;
 use utils::*;
 
+//  Optional arguments given as `--name value` pairs in any order
+fn flags<'a, const N: usize>(options: &[&'a str], names: [&str; N]) -> [Option<&'a str>; N] {
+    let mut values = [None; N];
+    for pair in options.chunks(2) {
+        let named = names
+            .iter()
+            .position(|name| pair[0].strip_prefix("--") == Some(name));
+
+        match (named, pair) {
+            (Some(i), [_, value]) if values[i].is_none() => values[i] = Some(*value),
+            _ => panic!("Invalid arguments"),
+        }
+    }
+    values
+}
+
 fn main() {
     let collected = env::args().collect::<Vec<_>>();
     let args = &collected[1..]
@@ -39,28 +55,95 @@ fn main() {
                 }
                 ["gen-mod", map_id, original, temp, comment] => {
                     project_manager
-                        .gen_mod(map_id, original, temp, comment)
+                        .gen_mod(map_id, original, temp, comment, None)
                         .context("Failed to generate mod")
                         .unwrap();
                 }
-                ["view-mod", map_id, original, mod_id, config] => {
+                ["gen-mod", map_id, original, temp, comment, config] => {
                     project_manager
-                        .view_mod(map_id, original, mod_id, config)
+                        .gen_mod(map_id, original, temp, comment, Some(config))
+                        .context("Failed to generate mod")
+                        .unwrap();
+                }
+                ["view-mod", map_id, original, mod_id, config, options @ ..] => {
+                    let [inflation] = flags(options, ["inflation"]);
+                    project_manager
+                        .view_mod(map_id, original, mod_id, config, inflation)
                         .context("Failed to inflate minimal from mod")
                         .unwrap();
                 }
-                ["try-fold", map_id, original, selected, config] => {
+                ["trace-mod", map_id, original, mod_id, config, options @ ..] => {
+                    let [inflation] = flags(options, ["inflation"]);
+                    project_manager
+                        .trace_mod(map_id, original, mod_id, config, inflation)
+                        .context("Failed to trace minimal from mod")
+                        .unwrap();
+                }
+                ["summarize-mod", map_id, original, mod_id, config] => {
+                    project_manager
+                        .summarize_mod(map_id, original, mod_id, config)
+                        .context("Failed to summarize mod")
+                        .unwrap();
+                }
+                ["locate-mod", map_id, original, mod_id, config] => {
                     project_manager
-                        .try_fold(map_id, original, selected, config)
+                        .locate_mod(map_id, original, mod_id, config)
+                        .context("Failed to locate changes")
+                        .unwrap();
+                }
+                ["try-fold", map_id, original, selected, config, options @ ..] => {
+                    let [resolution, inflation] = flags(options, ["resolution", "inflation"]);
+                    project_manager
+                        .try_fold(map_id, original, selected, config, resolution, inflation)
                         .context("Failed to fold mods")
                         .unwrap();
                 }
+                ["conflicts", map_id, original, selected, config, options @ ..] => {
+                    let [resolution, inflation] = flags(options, ["resolution", "inflation"]);
+                    project_manager
+                        .report_conflicts(map_id, original, selected, config, resolution, inflation)
+                        .context("Failed to report conflicts")
+                        .unwrap();
+                }
+                ["unfold", map_id, original, selected, config] => {
+                    project_manager
+                        .unfold(map_id, original, selected, config)
+                        .context("Failed to unfold mod")
+                        .unwrap();
+                }
+                ["merge-mod", map_id, original, selected, config, options @ ..] => {
+                    let [resolution] = flags(options, ["resolution"]);
+                    project_manager
+                        .merge_mod(map_id, original, selected, config, resolution)
+                        .context("Failed to merge mod")
+                        .unwrap();
+                }
                 ["amend-mod", map_id, original, selected, comment] => {
                     project_manager
-                        .amend_mod(map_id, original, selected, comment)
+                        .amend_mod(map_id, original, selected, comment, None)
+                        .context("Failed to amend mod")
+                        .unwrap();
+                }
+                ["amend-mod", map_id, original, selected, comment, config] => {
+                    project_manager
+                        .amend_mod(map_id, original, selected, comment, Some(config))
                         .context("Failed to amend mod")
                         .unwrap();
                 }
+                ["view-gen-mod", map_id, original, mod_id, comment, config, options @ ..] => {
+                    let [inflation] = flags(options, ["inflation"]);
+                    project_manager
+                        .gen_mod_from_view(map_id, original, mod_id, comment, config, inflation)
+                        .context("Failed to generate mod from view")
+                        .unwrap();
+                }
+                ["view-amend-mod", map_id, original, selected, comment, config, options @ ..] => {
+                    let [inflation] = flags(options, ["inflation"]);
+                    project_manager
+                        .amend_mod_from_view(map_id, original, selected, comment, config, inflation)
+                        .context("Failed to amend mod from view")
+                        .unwrap();
+                }
                 ["skip-mod", map_id, selected] => {
                     project_manager
                         .skip_mod(map_id, selected)
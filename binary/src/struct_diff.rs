@@ -2,8 +2,10 @@ mod divider;
 mod structure;
 mod diff;
 mod range_vec;
+mod merge;
 
 pub use divider::*;
 pub use structure::*;
 pub use crate::struct_diff::diff::*;
 pub use range_vec::*;
+pub use merge::*;
@@ -1,50 +1,70 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
+use super::{merge::Resolved, structure::Config};
 use crate::utils::*;
 use std::fmt::Debug;
 use serde::{Serialize, Deserialize};
 use slice_diff_patch::*;
 use diff::Result as DiffResult;
 use itertools::Itertools;
-use itertools::FoldWhile::{Continue, Done};
 
-trait ChangeExt {
-    fn offset(&self) -> usize;
-    fn with_offest_as(self, offset: isize) -> Self;
+//  A unit of a patched text, either one of the units it was patched from or a new one
+#[derive(Debug, Clone, PartialEq)]
+enum Unit {
+    Kept(usize),
+    New(String),
 }
 
-impl<T> ChangeExt for Change<T> 
-where
-    T: Debug + PartialEq + Clone
-{
-    fn offset(&self) -> usize {
-        match self {
-            Change::Remove(i) => *i,
-            Change::Insert((i, _)) => *i,
-            Change::Update((i, _)) => *i,
+fn replay(mut units: Vec<Unit>, changes: &[Change<String>]) -> Vec<Unit> {
+    for change in changes {
+        match change {
+            Change::Remove(i) => {
+                units.remove(*i);
+            }
+            Change::Insert((i, unit)) => units.insert(*i, Unit::New(unit.clone())),
+            Change::Update((i, unit)) => units[*i] = Unit::New(unit.clone()),
         }
     }
+    units
+}
 
-    fn with_offest_as(self, offset: isize) -> Self {
-        match self {
-            Change::Remove(_) => Change::Remove(offset as usize),
-            Change::Insert((_, s)) => Change::Insert((offset as usize, s)),
-            Change::Update((_, s)) => Change::Update((offset as usize, s)),
-        }
-    }
+//  Enough units for every change to land on one, whatever the length of the text
+fn bound(changes: &[Change<String>]) -> usize {
+    changes
+        .iter()
+        .map(|change| match change {
+            Change::Remove(i) | Change::Insert((i, _)) | Change::Update((i, _)) => *i,
+        })
+        .max()
+        .map_or(0, |i| i + 1) + changes.len()
 }
 
+//  Changes, removed and added index into units of the text. Units are lines unless
+//  spans holds the offsets the original was cut at, in which case they are pieces
+//  of text lined up with object boundaries (see Config::cuts). Resolved keeps track
+//  of the conflicts settled automatically while folding mods into this one.
 #[derive(Debug, Clone, PartialEq)]
 pub struct StructDiff {
     pub comment: String,
     pub changes: Vec<Change<String>>,
     pub removed: Vec<usize>,
-    pub added: Vec<usize>
+    pub added: Vec<usize>,
+    pub spans: Option<Vec<usize>>,
+    pub resolved: Vec<Resolved>,
 }
 
-impl StructDiff {
-    pub fn build_from(old: &str, new: &str, comment: &str) -> Self {
-        let diff = diff::lines(old, new);
+//  Cuts made for another version of the text can fall past its end or inside a character,
+//  so they're pulled back to the nearest character boundary rather than panicking
+fn cut(text: &str, cuts: &[usize]) -> Vec<String> {
+    [0].into_iter()
+        .chain(cuts.iter().map(|&cut| text.floor_char_boundary(cut)))
+        .chain([text.len()])
+        .tuple_windows()
+        .map(|(from, to)| text[from..to.max(from)].to_string())
+        .collect()
+}
 
+impl StructDiff {
+    fn removed_and_added<T>(diff: &[DiffResult<T>]) -> (Vec<usize>, Vec<usize>) {
         let removed = diff
             .iter()
             .filter(|d| !matches!(d, DiffResult::Right(_)))
@@ -59,6 +79,12 @@ impl StructDiff {
             .filter(|(_, line)| matches!(line, DiffResult::Right(_)))
             .map(|(i, _)| i);
 
+        (removed.collect(), added.collect())
+    }
+
+    pub fn build_from(old: &str, new: &str, comment: &str) -> Self {
+        let (removed, added) = Self::removed_and_added(&diff::lines(old, new));
+
         let changes = diff_diff(
             old.lines().map(|l| l.to_string()).collect::<Vec<_>>().as_ref(),
             new.lines().map(|l| l.to_string()).collect::<Vec<_>>().as_ref()
@@ -66,127 +92,233 @@ impl StructDiff {
 
         Self {
             comment: comment.to_string(),
-            removed: removed.collect(),
-            added: added.collect(),
-            changes
+            removed,
+            added,
+            changes,
+            spans: None,
+            resolved: vec![]
         }
     }
 
-    pub fn patch<L, S>(&self, original: L) -> Vec<String>
-    where
-        L: Iterator<Item = S> + Clone + Send + Sync,
-        S: AsRef<str> + Send
-    {
-        let original = original
-            .map(|l| l.as_ref().to_string())
+    //  For formats that put several objects on a line, e.g. minified JSON
+    pub fn build_from_spans(old: &str, new: &str, config: &Config, comment: &str) -> Self {
+        let cuts = config.cuts(old);
+        let old_units = cut(old, &cuts);
+        let new_units = cut(new, &config.cuts(new));
+
+        let (removed, added) = Self::removed_and_added(&diff::slice(&old_units, &new_units));
+
+        Self {
+            comment: comment.to_string(),
+            removed,
+            added,
+            changes: diff_diff(&old_units, &new_units),
+            spans: Some(cuts),
+            resolved: vec![]
+        }
+    }
+
+    //  Between units already cut the same way as this diff's
+    pub fn rebuilt(&self, old: &[String], new: &[String], comment: &str) -> Self {
+        let (removed, added) = Self::removed_and_added(&diff::slice(old, new));
+
+        Self {
+            comment: comment.to_string(),
+            removed,
+            added,
+            changes: diff_diff(old, new),
+            spans: self.spans.clone(),
+            resolved: self.resolved.clone()
+        }
+    }
+
+    //  The units a text is divided into for this diff
+    pub fn units(&self, text: &str) -> Vec<String> {
+        match &self.spans {
+            Some(cuts) => cut(text, cuts),
+            None => text.lines().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    //  Inverse of units
+    pub fn join(&self, units: &[String]) -> String {
+        match &self.spans {
+            Some(_) => units.concat(),
+            None => units.join("\n"),
+        }
+    }
+
+    pub fn patch_units(&self, units: &[String]) -> Vec<String> {
+        patch(units, &self.changes)
+    }
+
+    //  Patched text, which for spans keeps whatever the original ends with
+    pub fn patch(&self, original: &str) -> String {
+        self.join(&self.patch_units(&self.units(original)))
+    }
+
+    //  What the units patched from the first len units are. Units past the end of the
+    //  text are never touched, so they only ever end up past the end of the patched text.
+    fn trace(&self, len: usize) -> Vec<Unit> {
+        replay((0..len).map(Unit::Kept).collect(), &self.changes)
+    }
+
+    //  The diff that patches len units into the traced ones, with every removal before any insertion
+    fn from_trace(trace: &[Unit], len: usize, comment: &str, spans: Option<Vec<usize>>, resolved: Vec<Resolved>) -> Self {
+        let kept = trace
+            .iter()
+            .filter_map(|unit| match unit {
+                Unit::Kept(i) => Some(*i),
+                Unit::New(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let removed = (0..len)
+            .filter(|i| kept.binary_search(i).is_err())
+            .collect::<Vec<_>>();
+
+        let added = trace
+            .iter()
+            .positions(|unit| matches!(unit, Unit::New(_)))
             .collect::<Vec<_>>();
-        
-        patch(original.as_ref(), &self.changes)
+
+        let changes = removed
+            .iter()
+            .enumerate()
+            .map(|(shift, i)| Change::Remove(i - shift))
+            .chain(added.iter().map(|&i| match &trace[i] {
+                Unit::New(unit) => Change::Insert((i, unit.clone())),
+                Unit::Kept(_) => unreachable!(),
+            }))
+            .collect();
+
+        Self {
+            comment: comment.to_string(),
+            changes,
+            removed,
+            added,
+            spans,
+            resolved
+        }
     }
 
-    pub fn extend(&mut self, other: Self) { // this algorithm can be improved
-        {
-            let removed = self
-                .changes
-                .iter()
-                .filter(|c| matches!(c, Change::Remove(_)))
-                .map(|c| c.offset())
-                .collect::<HashSet<_>>();
-
-            let change_shifts = self
-                .changes
-                .iter()
-                .map(|change| match change {
-                    Change::Remove(i) => (*i, -1),
-                    Change::Insert((i, _)) => (*i, 1),
-                    Change::Update((i, _)) => (*i, 0)
-                })
-                .collect::<HashSet<_>>();
-
-            let de_duped = other.changes.iter().filter(
-                |change| match change {
-                    Change::Remove(i) => !removed.contains(i),
-                    _ => true
+    //  Patching the original units with the composition is the same as patching them with
+    //  this and then other. Units other puts back the way they were aren't removed or added.
+    pub fn compose(&self, other: &Self, original: &[String]) -> Self {
+        let len = bound(&self.changes) + bound(&other.changes);
+        let trace = replay(self.trace(len), &other.changes);
+
+        let mut composed = Self::from_trace(
+            &trace,
+            len,
+            &self.comment,
+            self.spans.clone(),
+            self.resolved.iter().chain(other.resolved.iter()).cloned().collect()
+        );
+
+        (composed.removed, composed.added) = Self::removed_and_added(&diff::slice(original, &composed.patch_units(original)));
+        composed
+    }
+
+    //  Undoes this diff, which needs the units it was made from for whatever it removed
+    pub fn invert(&self, original: &[String]) -> Self {
+        let trace = self.trace(original.len());
+        let mut patched = vec![None; original.len()];
+        for (at, unit) in trace.iter().enumerate() {
+            if let Unit::Kept(i) = unit {
+                patched[*i] = Some(at);
+            }
+        }
+
+        let inverse = original
+            .iter()
+            .zip(patched)
+            .map(|(unit, at)| at.map_or_else(|| Unit::New(unit.clone()), Unit::Kept))
+            .collect::<Vec<_>>();
+
+        Self::from_trace(&inverse, trace.len(), &self.comment, self.spans.clone(), vec![])
+    }
+
+    //  This diff made to apply after onto, both having been made from the same units.
+    //  Whatever onto removed stays removed, and units this adds go after any onto adds
+    //  in the same place.
+    pub fn rebase(&self, onto: &Self) -> Self {
+        let len = bound(&self.changes).max(bound(&onto.changes));
+        let ours = self.trace(len);
+        let theirs = onto.trace(len);
+
+        let kept = ours
+            .iter()
+            .filter_map(|unit| match unit {
+                Unit::Kept(i) => Some(*i),
+                Unit::New(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        //  Units this adds by the unit they come before, len for the end
+        let mut added = HashMap::<usize, Vec<Unit>>::new();
+        let mut next = len;
+        for unit in ours.iter().rev() {
+            match unit {
+                Unit::Kept(i) => next = *i,
+                Unit::New(_) => added.entry(next).or_default().insert(0, unit.clone()),
+            }
+        }
+
+        let mut rebased = vec![];
+        let mut passed = 0;
+        for (at, unit) in theirs.iter().enumerate() {
+            if let Unit::Kept(i) = unit {
+                for before in passed..=*i {
+                    rebased.extend(added.remove(&before).unwrap_or_default());
                 }
-            );
-
-            let new_changes = de_duped.cloned().map(|change| {
-                let shifted = change_shifts.iter().fold_while(change.offset() as isize,
-                    |acc, (offset, shift)| {
-                        if (*offset as isize) < acc {
-                            Continue(acc + shift)
-                        } else {
-                            Done(acc)
-                        }
-                    }
-                );
-
-                change.with_offest_as(shifted.into_inner())
-            });
-
-            self.changes.extend_from_slice(
-                new_changes
-                    .collect::<Vec<_>>()
-                    .as_slice()
-            );
+                passed = i + 1;
+
+                if kept.binary_search(i).is_ok() {
+                    rebased.push(Unit::Kept(at));
+                }
+            } else {
+                rebased.push(Unit::Kept(at));
+            }
         }
 
-        {
-            let removed = self
-                .removed
-                .iter()
-                .collect::<HashSet<_>>();
-
-            let mut shifts = other
-                .removed
-                .iter()
-                .filter(|i| !removed.contains(i))
-                .map(|i| (*i, -1))
-                .chain(self.added.iter_mut().map(|i| (*i, 1)))
-                .collect::<Vec<_>>();
-
-            shifts.sort_by_key(|(i, _)| *i);
-
-            let new_removed = other.removed.iter().map(|removal| {
-                let shifted = shifts.iter().fold_while(*removal as isize,
-                    |acc, (_, shift)| {
-                        if (acc as isize) < acc {
-                            Continue(acc + shift)
-                        } else {
-                            Done(acc)
-                        }
-                    }
-                );
-
-                shifted.into_inner() as usize
-            });
-
-            let new_added = other.added.iter().map(|addition| {
-                let shift = shifts.iter().fold_while(*addition as isize,
-                    |acc, (_, shift)| {
-                        if (acc as isize) < acc {
-                            Continue(acc + shift)
-                        } else {
-                            Done(acc)
-                        }
-                    }
-                );
-
-                shift.into_inner() as usize
-            });
-
-            self.comment = "Super Mod".to_string();
-            self.removed.extend(new_removed.collect::<Vec<_>>().as_slice());
-            self.added.extend(new_added.collect::<Vec<_>>().as_slice());
-            self.removed.sort_unstable();
-            self.added.sort_unstable();
+        for before in passed..=len {
+            rebased.extend(added.remove(&before).unwrap_or_default());
         }
+
+        Self::from_trace(&rebased, theirs.len(), &self.comment, self.spans.clone(), self.resolved.clone())
+    }
+
+    //  This diff with the mod folded in as mod_id taken back out, both having been made from the
+    //  original units. Whatever else was folded in stays, along with the indices of the units
+    //  the mod added that something else folded in has since changed, which stay changed.
+    pub fn unfold(&self, folded: &Self, mod_id: &str, original: &[String]) -> (Self, Vec<usize>) {
+        let with_folded = folded.patch_units(original);
+        let others = self.rebuilt(&with_folded, &self.patch_units(original), "");
+        let undone = self.compose(&folded.invert(original).rebase(&others), original);
+
+        let overwritten = folded.added
+            .iter()
+            .filter(|i| others.removed.binary_search(i).is_ok())
+            .copied()
+            .collect();
+
+        let mut unfolded = self.rebuilt(original, &undone.patch_units(original), &self.comment);
+        unfolded.resolved.retain(|resolved| resolved.mod_id != mod_id);
+        (unfolded, overwritten)
+    }
+
+    //  Folds other in, both having been made from the original units
+    pub fn extend(&mut self, other: Self, original: &[String]) {
+        *self = self.compose(&other.rebase(self), original);
+        self.comment = "Super Mod".to_string();
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use super::super::merge::Keep;
     use indoc::indoc;
 
     const ORIGINAL: &str = indoc!(
@@ -246,16 +378,62 @@ mod test {
 
     #[test]
     fn patch() {
-        let remade = StructDiff::build_from(ORIGINAL, MODDED, "").patch(ORIGINAL.lines());
+        let remade = StructDiff::build_from(ORIGINAL, MODDED, "").patch(ORIGINAL);
 
         let expected_remade = MODDED
             .lines()
             .map(|s| s.to_string())
-            .collect::<Vec<_>>();
+            .collect::<Vec<_>>()
+            .join("\n");
 
         assert_eq!(remade, expected_remade);
     }
 
+    const COMPACT_ORIGINAL: &str = r#"{"speed":1,"objs":[{"lane":0,"time":0},{"lane":1,"time":0},{"lane":2,"time":0}]}"#;
+    const COMPACT_MODDED: &str = r#"{"speed":1,"objs":[{"lane":0,"time":0},{"lane":1,"time":4},{"lane":2,"time":0}]}"#;
+
+    fn compact_config() -> Config {
+        let config_json = indoc!(r#"
+            {
+                "keys": [],
+                "filter": {
+                    "prefix": "\"objs\":",
+                    "open": "\\[",
+                    "close": "\\]"
+                },
+                "expander": {
+                    "prefix": "\\{\"lane\"",
+                    "open": "\\{",
+                    "close": "\\}",
+                    "quote": "\""
+                }
+            }
+        "#);
+
+        serde_json::from_str::<ConfigDef>(config_json)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn spans() {
+        let modded = StructDiff::build_from_spans(COMPACT_ORIGINAL, COMPACT_MODDED, &compact_config(), "");
+        let units = modded.units(COMPACT_ORIGINAL);
+
+        assert_eq!(modded.removed.len(), 1);
+        assert_eq!(modded.added.len(), 1);
+        assert_eq!(units[modded.removed[0]], r#"{"lane":1,"time":0}"#);
+        assert_eq!(modded.patch_units(&units)[modded.added[0]], r#"{"lane":1,"time":4}"#);
+        assert_eq!(modded.patch(COMPACT_ORIGINAL), COMPACT_MODDED);
+        assert_eq!(modded.patch(&format!("{COMPACT_ORIGINAL}\n")), format!("{COMPACT_MODDED}\n"));
+    }
+
+    #[test]
+    fn cut_anywhere() {
+        assert_eq!(cut("aé", &[2, 5]), ["a", "é", ""]);
+        assert_eq!(cut("ab", &[1, 1]), ["a", "", "b"]);
+    }
+
     const ALPHA_ORIGINAL: &str = indoc!(
         "a
         b
@@ -292,13 +470,103 @@ mod test {
         f"
     );
 
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn compose() {
+        let first = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_1, "");
+        let second = StructDiff::build_from(ALPHA_MODDED_1, EXPECTED_ALPHA_1, "");
+        let composed = first.compose(&second, &lines(ALPHA_ORIGINAL));
+        let expected = StructDiff::build_from(ALPHA_ORIGINAL, EXPECTED_ALPHA_1, "");
+
+        assert_eq!(composed.patch(ALPHA_ORIGINAL), EXPECTED_ALPHA_1);
+        assert_eq!((composed.removed, composed.added), (expected.removed, expected.added));
+
+        let modded = StructDiff::build_from(ORIGINAL, MODDED, "");
+        let reverted = StructDiff::build_from(MODDED, ORIGINAL, "");
+        let composed = modded.compose(&reverted, &lines(ORIGINAL));
+        assert_eq!(composed.patch(ORIGINAL), ORIGINAL);
+        assert_eq!((composed.removed, composed.added), (vec![], vec![]));
+    }
+
+    #[test]
+    fn invert() {
+        let modded = StructDiff::build_from(ORIGINAL, MODDED, "");
+        let inverse = modded.invert(&lines(ORIGINAL));
+
+        assert_eq!(inverse.patch(MODDED), ORIGINAL);
+        assert_eq!(modded.compose(&inverse, &lines(ORIGINAL)).patch(ORIGINAL), ORIGINAL);
+        assert_eq!((inverse.removed, inverse.added), (modded.added, modded.removed));
+    }
+
+    #[test]
+    fn rebase() {
+        let modded_1 = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_1, "");
+        let modded_2 = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_2, "");
+
+        let rebased = modded_2.rebase(&modded_1);
+        assert_eq!(rebased.patch(ALPHA_MODDED_1), EXPECTED_ALPHA_1);
+        assert_eq!(rebased.added, vec![3]);
+
+        let rebased = modded_1.rebase(&modded_2);
+        assert_eq!(rebased.patch(ALPHA_MODDED_2), EXPECTED_ALPHA_1);
+        assert_eq!(rebased.removed, vec![1]);
+    }
+
+    #[test]
+    fn extend_repeatedly() {
+        let mut folded = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_1, "");
+        folded.extend(StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_2, ""), &lines(ALPHA_ORIGINAL));
+        folded.extend(StructDiff::build_from(ALPHA_ORIGINAL, &ALPHA_ORIGINAL.replace('f', "F"), ""), &lines(ALPHA_ORIGINAL));
+        folded.extend(StructDiff::build_from(ALPHA_ORIGINAL, &ALPHA_ORIGINAL.replace("a\n", ""), ""), &lines(ALPHA_ORIGINAL));
+
+        let expected = "c\nd\nG\ne\nF";
+        let rebuilt = StructDiff::build_from(ALPHA_ORIGINAL, expected, "");
+        assert_eq!(folded.patch(ALPHA_ORIGINAL), expected);
+        assert_eq!((folded.removed, folded.added), (rebuilt.removed, rebuilt.added));
+    }
+
+    #[test]
+    fn unfold() {
+        let modded_1 = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_1, "first");
+        let modded_2 = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_2, "second");
+        let mut folded = modded_1.clone();
+        folded.extend(modded_2.clone(), &lines(ALPHA_ORIGINAL));
+
+        let (unfolded, overwritten) = folded.unfold(&modded_1, "1", &lines(ALPHA_ORIGINAL));
+        assert_eq!(unfolded.patch(ALPHA_ORIGINAL), ALPHA_MODDED_2);
+        assert!(overwritten.is_empty());
+
+        let (unfolded, _) = folded.unfold(&modded_2, "2", &lines(ALPHA_ORIGINAL));
+        assert_eq!(unfolded.patch(ALPHA_ORIGINAL), ALPHA_MODDED_1);
+
+        //  Settlements are dropped by mod, whatever the mods were called
+        let settled = |mod_id: &str| Resolved {
+            keys: vec![],
+            kept: Keep::Left,
+            mod_id: mod_id.to_string(),
+            comment: "same".to_string(),
+        };
+        folded.resolved = vec![settled("1"), settled("2")];
+        let (unfolded, _) = folded.unfold(&modded_2, "2", &lines(ALPHA_ORIGINAL));
+        assert_eq!(unfolded.resolved, vec![settled("1")]);
+
+        //  A later fold changing the line the unfolded mod added keeps its change
+        let folded = StructDiff::build_from(ALPHA_ORIGINAL, &EXPECTED_ALPHA_1.replace('G', "H"), "Super Mod");
+        let (unfolded, overwritten) = folded.unfold(&modded_2, "2", &lines(ALPHA_ORIGINAL));
+        assert_eq!(unfolded.patch(ALPHA_ORIGINAL), "a\nc\nd\nH\ne\nf");
+        assert_eq!(overwritten, vec![4]);
+    }
+
     #[test]
     fn merge_mods() {
         let mut modded_1 = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_1, "");
         let modded_2 = StructDiff::build_from(ALPHA_ORIGINAL, ALPHA_MODDED_2, "");
 
-        modded_1.extend(modded_2);
-        let patched = modded_1.patch(ALPHA_ORIGINAL.lines()).join("\n");
+        modded_1.extend(modded_2, &lines(ALPHA_ORIGINAL));
+        let patched = modded_1.patch(ALPHA_ORIGINAL);
         assert_eq!(patched, EXPECTED_ALPHA_1);
     }
 }
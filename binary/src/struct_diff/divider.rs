@@ -1,7 +1,205 @@
+mod external;
+mod indented;
+mod json;
+mod table;
+mod xml;
+
 use super::range_vec::InclRange;
 use super::range_vec::*;
-use rayon::prelude::*;
 use regex::Regex;
+use std::{borrow::Cow, collections::HashMap};
+use thiserror::Error;
+
+pub use external::Outputs;
+pub use json::Selector;
+pub use table::Header;
+pub use xml::XmlPath;
+
+//  Parts of the text that delimiters should not be counted in.
+//  A quoted literal is closed by the same text that opened it so `"it's"` stays one literal,
+//  and ends with its line if it isn't. Escapes only apply inside literals and comments run
+//  to the end of their match.
+#[derive(Debug, Clone, Default)]
+pub struct Ignore {
+    pub quote: Option<Regex>,
+    pub escape: Option<Regex>,
+    pub comment: Option<Regex>,
+}
+
+impl Ignore {
+    //  Where the literals and comments in the text are, in order, along with where the
+    //  literals left open at the end of their line start. Comments can span lines, so this
+    //  runs over the whole text at once, but a stray quote only hides the rest of its line.
+    fn stretches(&self, text: &str) -> (Vec<(usize, usize)>, Vec<usize>) {
+        if self.quote.is_none() && self.comment.is_none() {
+            return (vec![], vec![]);
+        }
+
+        let find = |regex: &Option<Regex>, at: usize| regex
+            .as_ref()
+            .and_then(|regex| regex.find_at(text, at))
+            .map(|found| {
+                //  Empty matches still have to make progress
+                let next = text[found.start()..]
+                    .chars()
+                    .next()
+                    .map_or(text.len(), |c| found.start() + c.len_utf8());
+
+                (found.start(), found.end().max(next))
+            });
+
+        let mut stretches = vec![];
+        let mut unclosed = vec![];
+        let mut pos = 0;
+        while pos < text.len() {
+            let quote = find(&self.quote, pos);
+            let comment = find(&self.comment, pos);
+
+            match (quote, comment) {
+                (Some((q_start, q_end)), comment) if comment.is_none_or(|(c_start, _)| q_start < c_start) => {
+                    let opener = &text[q_start..q_end];
+                    let line_end = text[q_end..].find('\n').map_or(text.len(), |i| q_end + i);
+                    let mut end = None;
+                    let mut at = q_end;
+
+                    while at < line_end {
+                        let escape = find(&self.escape, at).filter(|&(_, e_end)| e_end <= line_end);
+                        let closer = text[at..line_end].find(opener).map(|i| (at + i, at + i + opener.len()));
+
+                        match (escape, closer) {
+                            (Some((e_start, e_end)), Some((c_start, _))) if e_start <= c_start => at = e_end,
+                            (_, Some((_, c_end))) => {
+                                end = Some(c_end);
+                                break;
+                            }
+                            (_, None) => break,
+                        }
+                    }
+
+                    let end = end.unwrap_or_else(|| {
+                        unclosed.push(q_start);
+                        line_end
+                    });
+
+                    stretches.push((q_start, end));
+                    pos = end;
+                }
+                (_, Some((c_start, c_end))) => {
+                    stretches.push((c_start, c_end));
+                    pos = c_end;
+                }
+                _ => break,
+            }
+        }
+
+        (stretches, unclosed)
+    }
+
+    //  Blanks out literals and comments with spaces so offsets into the text stay valid.
+    //  Line breaks are left alone so the masked text splits into the same lines.
+    fn mask<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let (stretches, _) = self.stretches(text);
+        if stretches.is_empty() {
+            return Cow::Borrowed(text);
+        }
+
+        let mut masked = text.as_bytes().to_vec();
+        for (from, to) in stretches {
+            masked[from..to]
+                .iter_mut()
+                .filter(|byte| **byte != b'\n')
+                .for_each(|byte| *byte = b' ');
+        }
+
+        //  Only whole characters are blanked so this can't fail
+        Cow::Owned(String::from_utf8(masked).unwrap_or_else(|_| text.to_string()))
+    }
+
+    //  Whether something found at offset is inside a literal or comment. Starting right where
+    //  one opens doesn't count, so a prefix can still begin with a quote.
+    fn hides(stretches: &[(usize, usize)], offset: usize) -> bool {
+        let before = stretches.partition_point(|&(from, _)| from < offset);
+        0 < before && offset < stretches[before - 1].1
+    }
+}
+
+//  Line numbers are stored from 0 but reported from 1 to match what editors show.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum Diagnostic {
+    #[error("object starting at line {} is never closed", .line + 1)]
+    Unclosed { line: usize },
+    #[error("stray close at line {}", .line + 1)]
+    StrayClose { line: usize },
+    #[error("literal at line {} is never closed", .line + 1)]
+    UnclosedLiteral { line: usize },
+    #[error("line {} is not indented by a whole number of indents", .line + 1)]
+    InconsistentIndent { line: usize },
+    #[error("could not parse line {}: {reason}", .line + 1)]
+    Unparsable { line: usize, reason: String },
+    #[error("line {}: {message}", .line + 1)]
+    Reported { line: usize, message: String },
+    #[error("external divider failed: {reason}")]
+    External { reason: String },
+}
+
+impl Diagnostic {
+    pub fn line(&self) -> usize {
+        match self {
+            | Self::Unclosed { line }
+            | Self::StrayClose { line }
+            | Self::UnclosedLiteral { line }
+            | Self::InconsistentIndent { line }
+            | Self::Unparsable { line, .. }
+            | Self::Reported { line, .. } => *line,
+            Self::External { .. } => 0,
+        }
+    }
+}
+
+//  Maps byte offsets to line indices for dividers that work on the whole text
+fn line_index(text: &str) -> impl Fn(usize) -> usize {
+    let starts = RangeVec::line_spans(text)
+        .iter()
+        .map(|span| span.lower)
+        .collect::<Vec<_>>();
+
+    move |offset: usize| starts.partition_point(|&start| start <= offset).saturating_sub(1)
+}
+
+//  keys holds the keys a divider found for its objects itself, if it can, and kinds
+//  the labels captured for them by a group named kind, like (?P<kind>press|hold)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divided {
+    pub ranges: RangeVec,
+    pub keys: Vec<(InclRange, Vec<String>)>,
+    pub kinds: Vec<(InclRange, String)>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Divided {
+    //  Between line indices and byte offsets
+    fn map_ranges<F>(self, convert: F) -> Self
+    where
+        F: Fn(InclRange) -> InclRange
+    {
+        Self {
+            ranges: self.ranges
+                .iter()
+                .map(|&range| convert(range))
+                .collect::<Vec<_>>()
+                .into(),
+            keys: self.keys
+                .into_iter()
+                .map(|(range, keys)| (convert(range), keys))
+                .collect(),
+            kinds: self.kinds
+                .into_iter()
+                .map(|(range, kind)| (convert(range), kind))
+                .collect(),
+            diagnostics: self.diagnostics
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum Divider {
@@ -9,6 +207,7 @@ pub enum Divider {
         prefix: Regex,
         open: Regex,
         close: Regex,
+        ignore: Ignore,
     },
     Headings {
         fuzzed: Regex,
@@ -18,90 +217,383 @@ pub enum Divider {
     Enclosures {
         top: Regex,
         bottom: Regex,
+        ignore: Ignore,
+    },
+    Json {
+        selector: Selector,
+    },
+    Xml {
+        path: XmlPath,
+    },
+    Indented {
+        block: Regex,
+        level: Option<usize>,
+    },
+    Table {
+        start: Regex,
+        separator: String,
+        header: bool,
+    },
+    External {
+        command: String,
+        args: Vec<String>,
+        outputs: Outputs,
     },
 }
 
 //  Todo:
-//      - Docmuent this exhaustively
+//      - Document this exhaustively
 //      - Test for edge cases
-//      - Add error reporting
 //
 impl Divider {
-    fn balancer<'a, I, S>(&'a self, iter: I) -> Box<dyn Fn(&usize) -> usize + 'a>
+    fn indent_depth(indent: &str, line: &str) -> usize {
+        if indent.is_empty() {
+            return 0;
+        }
+
+        line.as_bytes()
+            .windows(indent.len())
+            .step_by(indent.len())
+            .take_while(|&contents| *contents == *indent.as_bytes())
+            .count()
+    }
+
+    //  Every object closes on the first line where the running depth falls back to what it was
+    //  just before the object's first open. Objects that are still open are kept on a stack
+    //  ordered by that depth so each line only has to look at the top of it.
+    //  Unclosed objects still run to the last line so the rest of the file isn't lost,
+    //  but they are reported alongside closes that don't match any open.
+    fn balanced<I, S>(start: &Regex, open: &Regex, close: &Regex, ignore: &Ignore, lines: I) -> Divided
     where
-        I: Iterator<Item = S> + Clone + 'a,
-        S: AsRef<str> 
+        I: Iterator<Item = S>,
+        S: AsRef<str>
     {
-        match self {
-            | Self::Delimited { open, close, .. }
-            | Self::Enclosures { top: open, bottom: close } => {
-                Box::new(move |&start: &usize| {
-                    let mut depth: isize = 0;
-                    let iter = iter.clone().enumerate();
-
-                    iter.clone()
-                        .skip(start)
-                        .skip_while(|(_, line)| !open.is_match(line.as_ref())) // for patterns with prefixes followed by delimiters on a newline
-                        .find_map(|(index, line)| {
-                            let first = open.find_iter(line.as_ref()).count() as isize;
-                            let second = close.find_iter(line.as_ref()).count() as isize;
-                            depth += first - second;
-                            (depth <= 0).then(|| index)
-                        })
-                        .unwrap_or_else(|| iter.count() - 1)
-                })
+        let lines = lines.collect::<Vec<_>>();
+        let text = lines.iter().map(|line| line.as_ref()).collect::<Vec<_>>().join("\n");
+        let (stretches, unclosed_literals) = ignore.stretches(&text);
+        let masked = ignore.mask(&text);
+        let line_of = line_index(&text);
+
+        let mut ranges = vec![];
+        let mut diagnostics = unclosed_literals
+            .into_iter()
+            .map(|start| Diagnostic::UnclosedLiteral { line: line_of(start) })
+            .collect::<Vec<_>>();
+        let mut pending = vec![];                   // started but not opened yet
+        let mut stack: Vec<(usize, isize)> = vec![]; // (start, depth before first open)
+        let mut depth: isize = 0;
+        let mut last = 0;
+        let mut offset = 0;
+
+        for (index, (line, masked)) in lines.iter().zip(masked.split('\n')).enumerate() {
+            let line = line.as_ref();
+            last = index;
+
+            if start.find_iter(line).any(|found| !Ignore::hides(&stretches, offset + found.start())) {
+                pending.push(index);
+            }
+
+            offset += line.len() + 1;
+            let opens = open.find_iter(masked).count() as isize;
+
+            if 0 < opens {
+                stack.extend(pending.drain(..).map(|start| (start, depth)));
+            }
+
+            depth += opens - close.find_iter(masked).count() as isize;
+
+            if depth < 0 {
+                diagnostics.push(Diagnostic::StrayClose { line: index });
+                depth = 0;
             }
-            Self::Headings { fuzzed, indent, .. } => {
-                let indent_depth = move |line: &str| {
-                    indent.is_empty().then(|| 0).unwrap_or_else(|| {
-                        line.as_bytes()
-                            .windows(indent.len())
-                            .step_by(indent.len())
-                            .take_while(|&contents| *contents == *indent.as_bytes())
-                            .count()
-                    })
-                };
-
-                Box::new(move |&start: &usize| -> usize {
-                    let depth = iter
-                        .clone()
-                        .nth(start)
-                        .map_or(iter.clone().count(), |s| indent_depth(s.as_ref()));
-
-                    iter.clone()
-                        .enumerate()
-                        .skip(start + 1)
-                        .find_map(|(index, line)| {
-                            let line = line.as_ref();
-                            (fuzzed.is_match(line) && indent_depth(line) == depth).then(|| index - 1)
-                        })
-                        .unwrap_or_else(|| iter.clone().count() - 1)
-                })
+
+            while let Some(&(start, base)) = stack.last() {
+                if depth <= base {
+                    ranges.push(InclRange::new(start, index));
+                    stack.pop();
+                } else {
+                    break;
+                }
             }
         }
+
+        let unclosed = stack
+            .into_iter()
+            .map(|(start, _)| start)
+            .chain(pending)
+            .collect::<Vec<_>>();
+
+        diagnostics.extend(unclosed.iter().map(|&line| Diagnostic::Unclosed { line }));
+        ranges.extend(unclosed.into_iter().map(|start| InclRange::new(start, last)));
+
+        Divided {
+            ranges: ranges.into(),
+            keys: vec![],
+            kinds: vec![],
+            diagnostics
+        }
     }
 
-    pub fn divide<L, S>(&self, lines: L) -> RangeVec 
+    //  A heading runs until the line before the next heading at the same indent depth.
+    fn headings<I, S>(start: &Regex, fuzzed: &Regex, indent: &str, lines: I) -> Divided
     where
-        L: Iterator<Item = S> + Clone + Send + Sync,
-        S: AsRef<str> + Send
+        I: Iterator<Item = S>,
+        S: AsRef<str>
     {
-        let start_pattern = match self {
-            Self::Enclosures { top, .. } => top,
+        let mut ranges = vec![];
+        let mut diagnostics = vec![];
+        let mut open: HashMap<usize, Vec<usize>> = HashMap::new(); // depth -> starts
+        let mut last = 0;
+
+        for (index, line) in lines.enumerate() {
+            let line = line.as_ref();
+            last = index;
+
+            if fuzzed.is_match(line) {
+                let remainder = &line[Self::indent_depth(indent, line) * indent.len()..];
+                if !indent.is_empty() && remainder.starts_with([' ', '\t']) {
+                    diagnostics.push(Diagnostic::InconsistentIndent { line: index });
+                }
+
+                let closed = open
+                    .remove(&Self::indent_depth(indent, line))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|start| InclRange::new(start, index - 1));
+
+                ranges.extend(closed);
+            }
+
+            if start.is_match(line) {
+                open.entry(Self::indent_depth(indent, line))
+                    .or_default()
+                    .push(index);
+            }
+        }
+
+        ranges.extend(
+            open.into_values()
+                .flatten()
+                .map(|start| InclRange::new(start, last))
+        );
+
+        Divided {
+            ranges: ranges.into(),
+            keys: vec![],
+            kinds: vec![],
+            diagnostics
+        }
+    }
+
+    //  The same balancing as above but over positions in the whole text
+    //  so objects that share a line still come out separately.
+    fn balanced_spans(start: &Regex, open: &Regex, close: &Regex, ignore: &Ignore, text: &str) -> Divided {
+        let line_of = line_index(text);
+
+        let (stretches, unclosed_literals) = ignore.stretches(text);
+        let masked = ignore.mask(text);
+
+        //  Starts sort before delimiters at the same position so a prefix can begin with its open
+        let mut events = start
+            .find_iter(text)
+            .filter(|found| !Ignore::hides(&stretches, found.start()))
+            .map(|found| (found.start(), 0, found.end()))
+            .chain(open.find_iter(&masked).map(|found| (found.start(), 1, found.end())))
+            .chain(close.find_iter(&masked).map(|found| (found.start(), 2, found.end())))
+            .collect::<Vec<_>>();
+
+        events.sort_unstable();
+
+        let mut ranges = vec![];
+        let mut diagnostics = unclosed_literals
+            .into_iter()
+            .map(|start| Diagnostic::UnclosedLiteral { line: line_of(start) })
+            .collect::<Vec<_>>();
+        let mut pending = vec![];
+        let mut stack: Vec<(usize, isize)> = vec![];
+        let mut depth: isize = 0;
+
+        for (position, event, end) in events {
+            match event {
+                0 => pending.push(position),
+                1 => {
+                    stack.extend(pending.drain(..).map(|start| (start, depth)));
+                    depth += 1;
+                }
+                _ => {
+                    depth -= 1;
+
+                    if depth < 0 {
+                        diagnostics.push(Diagnostic::StrayClose { line: line_of(position) });
+                        depth = 0;
+                    }
+
+                    while let Some(&(start, base)) = stack.last() {
+                        if depth <= base {
+                            ranges.push(InclRange::new(start, end.max(start + 1) - 1));
+                            stack.pop();
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        let unclosed = stack
+            .into_iter()
+            .map(|(start, _)| start)
+            .chain(pending)
+            .collect::<Vec<_>>();
+
+        diagnostics.extend(unclosed.iter().map(|&start| Diagnostic::Unclosed { line: line_of(start) }));
+        ranges.extend(unclosed.into_iter().map(|start| InclRange::new(start, text.len() - 1)));
+
+        Divided {
+            ranges: ranges.into(),
+            keys: vec![],
+            kinds: vec![],
+            diagnostics
+        }
+    }
+
+    fn parsed(spans: Result<Vec<InclRange>, (usize, String)>, text: &str) -> Divided {
+        match spans {
+            Ok(ranges) => Divided {
+                ranges: ranges.into(),
+                keys: vec![],
+                kinds: vec![],
+                diagnostics: vec![]
+            },
+            Err((offset, reason)) => Divided {
+                ranges: Vec::<InclRange>::new().into(),
+                keys: vec![],
+                kinds: vec![],
+                diagnostics: vec![Diagnostic::Unparsable { line: line_index(text)(offset), reason }]
+            },
+        }
+    }
+
+    //  Spans are byte offsets into the text, inclusive like line ranges.
+    //  Dividers that only ever cover whole lines are divided by line then widened.
+    pub fn divide_spans(&self, text: &str) -> Divided {
+        let mut divided = match self {
+            Self::Delimited { prefix, open, close, ignore } => {
+                Self::balanced_spans(prefix, open, close, ignore, text)
+            }
+            Self::Enclosures { top, bottom, ignore } => {
+                Self::balanced_spans(top, top, bottom, ignore, text)
+            }
+            Self::Json { selector } => Self::parsed(selector.spans(text), text),
+            //  The command gets the text as it is, line endings and all
+            Self::External { command, args, outputs } => {
+                let lines = RangeVec::line_spans(text);
+                outputs
+                    .run(command, args, text)
+                    .map_ranges(|range| InclRange::new(lines[range.lower].lower, lines[range.upper].upper))
+            }
+            Self::Headings { .. } | Self::Indented { .. } | Self::Table { .. } => {
+                let lines = RangeVec::line_spans(text);
+                self.divide(text.lines())
+                    .map_ranges(|range| InclRange::new(lines[range.lower].lower, lines[range.upper].upper))
+            }
+            Self::Xml { path } => path.divide(text),
+        };
+
+        if let (Self::Delimited { .. } | Self::Enclosures { .. }, Some(start)) = (self, self.kind_pattern()) {
+            divided.kinds = divided.ranges
+                .iter()
+                .filter_map(|&range| start
+                    .captures_at(text, range.lower)
+                    .filter(|captures| captures.get(0).is_some_and(|found| found.start() == range.lower))
+                    .and_then(|captures| captures.name("kind"))
+                    .map(|kind| (range, kind.as_str().to_string()))
+                )
+                .collect();
+        }
+
+        divided.ranges.sort_by_key(|range| (range.lower, range.upper));
+        divided.kinds.sort_by_key(|(range, _)| (range.lower, range.upper));
+        divided
+    }
+
+    //  The pattern objects start with, if it captures their kind
+    fn kind_pattern(&self) -> Option<&Regex> {
+        let start = match self {
             Self::Delimited { prefix, .. } => prefix,
+            Self::Enclosures { top, .. } => top,
             Self::Headings { fuzzed, strict, .. } => strict.as_ref().unwrap_or(fuzzed),
+            Self::Indented { block, .. } => block,
+            _ => return None,
         };
 
-        let chunk_filter = |(start, line): (usize, S)| start_pattern
-            .is_match(line.as_ref())
-            .then(|| InclRange::new(start, self.balancer(lines.clone())(&start)));
+        start
+            .capture_names()
+            .any(|name| name == Some("kind"))
+            .then_some(start)
+    }
+
+    //  Column names for keys to look cells up by, only tables have them
+    pub fn headers<L, S>(&self, lines: L) -> Vec<Header>
+    where
+        L: Iterator<Item = S>,
+        S: AsRef<str>
+    {
+        match self {
+            Self::Table { start, separator, header } => {
+                let lines = lines.collect::<Vec<_>>();
+                table::headers(start, separator, *header, &lines.iter().map(|line| line.as_ref()).collect::<Vec<_>>())
+            }
+            _ => vec![],
+        }
+    }
+
+    pub fn divide<L, S>(&self, lines: L) -> Divided
+    where
+        L: Iterator<Item = S>,
+        S: AsRef<str>
+    {
+        let owned = lines.collect::<Vec<_>>();
+        let lines = owned.iter().map(|line| line.as_ref()).collect::<Vec<_>>();
+
+        let mut divided = match self {
+            Self::Delimited { prefix, open, close, ignore } => {
+                Self::balanced(prefix, open, close, ignore, lines.iter())
+            }
+            Self::Enclosures { top, bottom, ignore } => {
+                Self::balanced(top, top, bottom, ignore, lines.iter())
+            }
+            Self::Headings { fuzzed, strict, indent } => {
+                Self::headings(strict.as_ref().unwrap_or(fuzzed), fuzzed, indent, lines.iter())
+            }
+            Self::Indented { block, level } => indented::blocks(block, *level, &lines),
+            Self::Table { start, separator, header } => table::rows(start, separator, *header, &lines),
+            Self::External { command, args, outputs } => outputs.run(command, args, &lines.join("\n")),
+            Self::Json { .. } | Self::Xml { .. } => {
+                let text = lines.join("\n");
+
+                let line_of = line_index(&text);
+                self.divide_spans(&text)
+                    .map_ranges(|span| InclRange::new(line_of(span.lower), line_of(span.upper)))
+            }
+        };
+
+        if let (false, Some(start)) = (matches!(self, Self::Json { .. } | Self::Xml { .. }), self.kind_pattern()) {
+            divided.kinds = divided.ranges
+                .iter()
+                .filter_map(|&range| start
+                    .captures(lines[range.lower])
+                    .and_then(|captures| captures.name("kind"))
+                    .map(|kind| (range, kind.as_str().to_string()))
+                )
+                .collect();
+        }
+
+        divided.ranges.sort_by_key(|range| (range.lower, range.upper));
+        divided.kinds.sort_by_key(|(range, _)| (range.lower, range.upper));
+        divided.diagnostics.sort_by_key(Diagnostic::line);
 
-        lines.clone()
-            .enumerate()
-            .par_bridge()
-            .filter_map(chunk_filter)
-            .collect::<Vec<_>>()
-            .into()
+        divided
     }
 }
 
@@ -205,9 +697,10 @@ mod tests {
             prefix: Regex::new(r"Foo").unwrap(),
             open: Regex::new(r"\{").unwrap(),
             close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore::default(),
         };
 
-        let mut results = divider.divide(DELIMITED_TEXT.lines());
+        let mut results = divider.divide(DELIMITED_TEXT.lines()).ranges;
         results.sort_by_key(|range| range.upper);
         assert_eq!(
             results,
@@ -215,6 +708,268 @@ mod tests {
         );
     }
 
+    #[test]
+    fn delimited_divider_nested() {
+        let divider = Divider::Delimited {
+            prefix: Regex::new(r"Foo|Bar").unwrap(),
+            open: Regex::new(r"\{").unwrap(),
+            close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore::default(),
+        };
+
+        assert_eq!(
+            divider.divide(DELIMITED_TEXT.lines()).ranges,
+            RangeVec::from([(0, 6), (2, 5), (7, 11), (12, 18), (14, 17)])
+        );
+    }
+
+    #[test]
+    fn delimited_divider_diagnostics() {
+        let divider = Divider::Delimited {
+            prefix: Regex::new(r"Foo").unwrap(),
+            open: Regex::new(r"\{").unwrap(),
+            close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore::default(),
+        };
+
+        let text = indoc!(
+            "Foo {
+                a: 0,
+            }
+            }
+            Foo {
+                a: 0,"
+        );
+
+        let divided = divider.divide(text.lines());
+        assert_eq!(divided.ranges, RangeVec::from([(0, 2), (4, 5)]));
+        assert_eq!(
+            divided.diagnostics,
+            vec![Diagnostic::StrayClose { line: 3 }, Diagnostic::Unclosed { line: 4 }]
+        );
+        assert_eq!(
+            divided.diagnostics[1].to_string(),
+            "object starting at line 5 is never closed"
+        );
+    }
+
+    #[test]
+    fn headings_divider_diagnostics() {
+        let divider = Divider::Headings {
+            fuzzed: Regex::new(r"\[[a-z|A-Z]*\]").unwrap(),
+            strict: None,
+            indent: String::from("    "),
+        };
+
+        let text = "[a]\n    [b]\n      [c]\n";
+        assert_eq!(
+            divider.divide(text.lines()).diagnostics,
+            vec![Diagnostic::InconsistentIndent { line: 2 }]
+        );
+    }
+
+    const LITERAL_TEXT: &str = indoc!(
+        r#"Foo {
+            a: "{ not an open",
+            b: 'it\'s } not a close',
+            // } nor is this
+            c: "\"}",
+        }
+        Foo {
+            a: 0, /* } */
+        }"#
+    );
+
+    #[test]
+    fn delimited_divider_literals() {
+        let divider = Divider::Delimited {
+            prefix: Regex::new(r"Foo").unwrap(),
+            open: Regex::new(r"\{").unwrap(),
+            close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore {
+                quote: Some(Regex::new(r#"["']"#).unwrap()),
+                escape: Some(Regex::new(r"\\.").unwrap()),
+                comment: Some(Regex::new(r"//.*|/\*.*?\*/").unwrap()),
+            },
+        };
+
+        let mut results = divider.divide(LITERAL_TEXT.lines()).ranges;
+        results.sort_by_key(|range| range.upper);
+        assert_eq!(
+            results,
+            RangeVec::from([(0, 5), (6, 8)])
+        );
+    }
+
+    #[test]
+    fn delimited_divider_stray_quote() {
+        let divider = Divider::Delimited {
+            prefix: Regex::new(r"Foo").unwrap(),
+            open: Regex::new(r"\{").unwrap(),
+            close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore {
+                quote: Some(Regex::new(r#"["']"#).unwrap()),
+                ..Ignore::default()
+            },
+        };
+
+        let text = "Foo {\n    name: Bob's {\n}\nFoo {\n}";
+        let divided = divider.divide(text.lines());
+        assert_eq!(divided.ranges, RangeVec::from([(0, 2), (3, 4)]));
+        assert_eq!(divided.diagnostics, vec![Diagnostic::UnclosedLiteral { line: 1 }]);
+
+        let divided = divider.divide_spans(text);
+        assert_eq!(divided.ranges.len(), 2);
+        assert_eq!(divided.diagnostics, vec![Diagnostic::UnclosedLiteral { line: 1 }]);
+    }
+
+    const COMMENTED_TEXT: &str = indoc!(
+        "Foo {
+            a: 0,
+        }
+        /* Foo {
+            b: 1,
+        */
+        Foo {
+            c: 2,
+        }"
+    );
+
+    #[test]
+    fn delimited_divider_block_comments() {
+        let divider = Divider::Delimited {
+            prefix: Regex::new(r"Foo").unwrap(),
+            open: Regex::new(r"\{").unwrap(),
+            close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore {
+                comment: Some(Regex::new(r"(?s)/\*.*?\*/").unwrap()),
+                ..Ignore::default()
+            },
+        };
+
+        assert_eq!(divider.divide(COMMENTED_TEXT.lines()).ranges, RangeVec::from([(0, 2), (6, 8)]));
+        assert!(divider.divide(COMMENTED_TEXT.lines()).diagnostics.is_empty());
+
+        let spans = divider.divide_spans(COMMENTED_TEXT).ranges;
+        let starts = spans.iter().map(|span| span.lower).collect::<Vec<_>>();
+        assert_eq!(starts, [0, COMMENTED_TEXT.rfind("Foo").unwrap()]);
+    }
+
+    #[test]
+    fn ignore_mask() {
+        let ignore = Ignore {
+            quote: Some(Regex::new(r#"""#).unwrap()),
+            escape: Some(Regex::new(r"\\.").unwrap()),
+            comment: Some(Regex::new(r"#.*").unwrap()),
+        };
+
+        assert_eq!(ignore.mask(r#"a: "x\"}", # }"#), "a:       ,    ");
+        assert_eq!(ignore.mask(r#"a: "unclosed {"#), "a:            ");
+        assert_eq!(ignore.mask("a: \"{\n}\", b"), "a:   \n}    ");
+        assert_eq!(Ignore::default().mask("a { }"), "a { }");
+    }
+
+    #[test]
+    fn delimited_divider_spans() {
+        let divider = Divider::Delimited {
+            prefix: Regex::new(r"\{").unwrap(),
+            open: Regex::new(r"\{").unwrap(),
+            close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore {
+                quote: Some(Regex::new(r#"""#).unwrap()),
+                ..Ignore::default()
+            },
+        };
+
+        let text = r#"[{"lane":0},{"lane":1,"tag":"}"},
+{"lane":2}]"#;
+
+        let spans = divider
+            .divide_spans(text)
+            .ranges
+            .iter()
+            .map(|span| &text[span.lower..=span.upper])
+            .collect::<Vec<_>>();
+
+        assert_eq!(spans, vec![r#"{"lane":0}"#, r#"{"lane":1,"tag":"}"}"#, r#"{"lane":2}"#]);
+    }
+
+    #[test]
+    fn json_divider() {
+        let divider = Divider::Json {
+            selector: Selector::parse("$.objs[*]").unwrap(),
+        };
+
+        let text = indoc!(
+            r#"{
+                "config": { "speed": 1 },
+                "objs": [
+                    { "lane": 0 }, { "lane": 1 },
+                    {
+                        "lane": 2
+                    }
+                ]
+            }"#
+        );
+
+        assert_eq!(divider.divide(text.lines()).ranges, RangeVec::from([(3, 3), (3, 3), (4, 6)]));
+
+        let divided = divider.divide("{\n\"objs\": [\n{]\n}".lines());
+        assert!(divided.ranges.is_empty());
+        assert!(matches!(divided.diagnostics[..], [Diagnostic::Unparsable { line: 2, .. }]));
+    }
+
+    #[test]
+    fn xml_divider() {
+        let divider = Divider::Xml {
+            path: XmlPath::parse("Foo").unwrap(),
+        };
+
+        let mut results = divider.divide(ENCLOSED_TEXT.lines()).ranges;
+        results.sort_by_key(|range| range.upper);
+        assert_eq!(
+            results,
+            RangeVec::from([(0, 6), (12, 18)])
+        );
+    }
+
+    #[test]
+    fn table_divider() {
+        let divider = Divider::Table {
+            start: Regex::new(r"\[entities\]").unwrap(),
+            separator: String::from(","),
+            header: false,
+        };
+
+        let divided = divider.divide(HEADINGS_TEXT.lines());
+        assert_eq!(divided.ranges, RangeVec::from([(5, 5), (6, 6)]));
+        assert!(divided.diagnostics.is_empty());
+    }
+
+    #[test]
+    fn captured_kinds() {
+        let divider = Divider::Delimited {
+            prefix: Regex::new(r"(?P<kind>Foo|Bar) \{").unwrap(),
+            open: Regex::new(r"\{").unwrap(),
+            close: Regex::new(r"\}").unwrap(),
+            ignore: Ignore::default(),
+        };
+
+        let kinds = |divided: Divided| divided.kinds
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect::<Vec<_>>();
+
+        assert_eq!(kinds(divider.divide(DELIMITED_TEXT.lines())), vec!["Foo", "Bar", "Bar", "Foo", "Bar"]);
+        assert_eq!(kinds(divider.divide_spans("Foo { a: Bar { } }")), vec!["Foo", "Bar"]);
+
+        let divider = Divider::Xml {
+            path: XmlPath::parse("*").unwrap(),
+        };
+
+        assert_eq!(kinds(divider.divide(ENCLOSED_TEXT.lines()))[..2], ["Foo", "val"]);
+    }
+
     #[test]
     fn headings_divider() {
         let divider = Divider::Headings {
@@ -223,7 +978,7 @@ mod tests {
             indent: String::from(""),
         };
 
-        let mut results = divider.divide(HEADINGS_TEXT.lines());
+        let mut results = divider.divide(HEADINGS_TEXT.lines()).ranges;
         results.sort_by_key(|range| range.upper);
         assert_eq!(results, RangeVec::from([(4, 6)]));
     }
@@ -236,7 +991,7 @@ mod tests {
             indent: String::from("    "),
         };
 
-        let mut results = divider.divide(INDENTED_HEADINGS_TEXT.lines());
+        let mut results = divider.divide(INDENTED_HEADINGS_TEXT.lines()).ranges;
         results.sort_by_key(|range| range.upper);
         assert_eq!(results, RangeVec::from([(4, 16)]));
     }
@@ -246,9 +1001,10 @@ mod tests {
         let divider = Divider::Enclosures {
             top: Regex::new(r"<Foo.*>").unwrap(),
             bottom: Regex::new(r"</Foo>").unwrap(),
+            ignore: Ignore::default(),
         };
 
-        let mut results = divider.divide(ENCLOSED_TEXT.lines());
+        let mut results = divider.divide(ENCLOSED_TEXT.lines()).ranges;
         results.sort_by_key(|range| range.upper);
         assert_eq!(
             results,
@@ -0,0 +1,211 @@
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Write},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    thread
+};
+
+use serde::Deserialize;
+
+use super::super::range_vec::InclRange;
+use super::{Diagnostic, Divided};
+
+//  What the command has to print. Lines are counted from 0 and ranges are inclusive.
+//  {
+//      "objects": [{ "lower": 3, "upper": 7, "keys": ["1", "400"], "kind": "hold" }],
+//      "diagnostics": [{ "line": 2, "message": "unknown note type" }]
+//  }
+#[derive(Deserialize)]
+struct Output {
+    objects: Vec<Object>,
+    #[serde(default)]
+    diagnostics: Vec<Reported>,
+}
+
+#[derive(Deserialize)]
+struct Object {
+    lower: usize,
+    upper: usize,
+    #[serde(default)]
+    keys: Vec<String>,
+    kind: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Reported {
+    line: usize,
+    message: String,
+}
+
+fn output(command: &str, args: &[String], text: &str) -> Result<Output, String> {
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", command, e))?;
+
+    //  Written from another thread so a command that prints before reading everything can't deadlock
+    let mut stdin = child.stdin.take().ok_or("could not open stdin")?;
+    let text = text.to_string();
+    let writer = thread::spawn(move || stdin.write_all(text.as_bytes()));
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("{} did not finish: {}", command, e))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "{} exited with {}: {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    //  Commands are free to stop reading once they've seen enough
+    match writer.join().map_err(|_| "could not write the map".to_string())? {
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => return Err(format!("could not write the map: {}", e)),
+        _ => {}
+    }
+
+    serde_json::from_slice(&output.stdout).map_err(|e| format!("{} printed invalid output: {}", command, e))
+}
+
+pub fn run(command: &str, args: &[String], text: &str) -> Divided {
+    let line_count = text.lines().count();
+
+    match output(command, args, text) {
+        Ok(output) => {
+            let (objects, out_of_bounds): (Vec<_>, Vec<_>) = output.objects
+                .into_iter()
+                .partition(|object| object.lower <= object.upper && object.upper < line_count);
+
+            let diagnostics = output.diagnostics
+                .into_iter()
+                .map(|reported| Diagnostic::Reported { line: reported.line, message: reported.message })
+                .chain(out_of_bounds.into_iter().map(|object| Diagnostic::Reported {
+                    line: object.lower,
+                    message: format!("object {}..={} is outside the map", object.lower, object.upper)
+                }))
+                .collect();
+
+            Divided {
+                ranges: objects
+                    .iter()
+                    .map(|object| InclRange::new(object.lower, object.upper))
+                    .collect::<Vec<_>>()
+                    .into(),
+                kinds: objects
+                    .iter()
+                    .filter_map(|object| object
+                        .kind
+                        .clone()
+                        .map(|kind| (InclRange::new(object.lower, object.upper), kind))
+                    )
+                    .collect(),
+                keys: objects
+                    .into_iter()
+                    .filter(|object| !object.keys.is_empty())
+                    .map(|object| (InclRange::new(object.lower, object.upper), object.keys))
+                    .collect(),
+                diagnostics
+            }
+        }
+        Err(reason) => Divided {
+            ranges: Vec::<InclRange>::new().into(),
+            keys: vec![],
+            kinds: vec![],
+            diagnostics: vec![Diagnostic::External { reason }]
+        },
+    }
+}
+
+//  What the command made of each text it was given. The same maps get divided over and over
+//  while working on a mod, so each text is only handed to the command once. Clones share it.
+#[derive(Debug, Clone, Default)]
+pub struct Outputs(Arc<Mutex<HashMap<String, Divided>>>);
+
+impl Outputs {
+    pub fn run(&self, command: &str, args: &[String], text: &str) -> Divided {
+        let cached = |outputs: &Self| outputs.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .get(text)
+            .cloned();
+
+        if let Some(divided) = cached(self) {
+            return divided;
+        }
+
+        let divided = run(command, args, text);
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(text.to_string(), divided.clone());
+
+        divided
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use super::super::Divider;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn external_objects() {
+        let script = r#"cat > /dev/null; echo '{
+            "objects": [{ "lower": 0, "upper": 1, "keys": ["a"] }, { "lower": 2, "upper": 9 }],
+            "diagnostics": [{ "line": 1, "message": "odd" }]
+        }'"#;
+
+        let divided = run("sh", &sh(script), "x\ny\nz");
+        assert_eq!(divided.ranges, [(0, 1)].into());
+        assert_eq!(divided.keys, vec![(InclRange::new(0, 1), vec!["a".to_string()])]);
+        assert_eq!(divided.diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn external_runs_once_per_text() {
+        let runs = std::env::temp_dir().join(format!("external_runs_{}", std::process::id()));
+        let script = format!(r#"cat > /dev/null; echo run >> {}; echo '{{ "objects": [] }}'"#, runs.display());
+        let outputs = Outputs::default();
+
+        for text in ["x", "x", "y", "x"] {
+            outputs.clone().run("sh", &sh(&script), text);
+        }
+
+        let count = std::fs::read_to_string(&runs).unwrap().lines().count();
+        std::fs::remove_file(&runs).unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn external_spans_get_the_text() {
+        let given = std::env::temp_dir().join(format!("external_given_{}", std::process::id()));
+        let script = format!(r#"cat > {}; echo '{{ "objects": [{{ "lower": 1, "upper": 1 }}] }}'"#, given.display());
+        let divider = Divider::External { command: "sh".to_string(), args: sh(&script), outputs: Outputs::default() };
+
+        let text = "x\r\ny\n";
+        let divided = divider.divide_spans(text);
+        let contents = std::fs::read_to_string(&given).unwrap();
+        std::fs::remove_file(&given).unwrap();
+
+        assert_eq!(contents, text);
+        assert_eq!(divided.ranges, [(3, 4)].into());
+    }
+
+    #[test]
+    fn external_failure() {
+        let divided = run("sh", &sh("echo broken >&2; exit 3"), "x");
+        assert!(divided.ranges.is_empty());
+        assert!(matches!(&divided.diagnostics[..], [Diagnostic::External { reason }] if reason.contains("broken")));
+    }
+}
@@ -0,0 +1,191 @@
+use itertools::Itertools;
+use regex::Regex;
+use std::{cmp::Reverse, collections::HashMap};
+
+use super::super::range_vec::InclRange;
+use super::{Diagnostic, Divided};
+
+//  Blocks are sequence items (- ...) and mapping keys with nothing after the colon,
+//  each running over the lines indented deeper than it. A key's block also takes
+//  sequence items at its own indent since YAML allows them to sit flush with the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Opener {
+    Item,
+    Key,
+}
+
+fn opener(content: &str) -> Option<Opener> {
+    let content = content
+        .split(" #")
+        .next()
+        .unwrap_or("")
+        .trim_end();
+
+    if content == "-" || content.starts_with("- ") {
+        Some(Opener::Item)
+    } else if content.ends_with(':') && !content.starts_with('#') {
+        Some(Opener::Key)
+    } else {
+        None
+    }
+}
+
+//  Indents are a tab each when the text is tab indented, otherwise the number of
+//  spaces lines most often step in by, so one misindented line can't throw it off.
+fn indent_width(lines: &[&str]) -> (char, usize) {
+    let lines = lines
+        .iter()
+        .filter(|line| !line.trim_start().is_empty() && !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>();
+
+    let indents = lines
+        .iter()
+        .map(|line| &line[..line.len() - line.trim_start().len()])
+        .collect::<Vec<_>>();
+
+    let tabs = indents.iter().filter(|indent| indent.starts_with('\t')).count();
+    let indented = indents.iter().filter(|indent| !indent.is_empty()).count();
+
+    if indented < tabs * 2 {
+        return ('\t', 1);
+    }
+
+    let mut steps = HashMap::<usize, usize>::new();
+    for (outer, inner) in indents.iter().map(|indent| indent.len()).tuple_windows() {
+        if outer < inner {
+            *steps.entry(inner - outer).or_default() += 1;
+        }
+    }
+
+    let width = steps
+        .into_iter()
+        .max_by_key(|&(step, count)| (count, Reverse(step)))
+        .map_or(1, |(step, _)| step);
+
+    (' ', width)
+}
+
+pub fn blocks(block: &Regex, level: Option<usize>, lines: &[&str]) -> Divided {
+    let (unit, width) = indent_width(lines);
+    let mut ranges = vec![];
+    let mut diagnostics = vec![];
+    let mut stack: Vec<(usize, usize, Opener)> = vec![]; // (start, indent, opener)
+    let mut last = 0;
+
+    let close = |(start, indent, _): (usize, usize, Opener), end: usize| {
+        let at_level = level.is_none_or(|level| indent / width == level);
+        (at_level && block.is_match(lines[start])).then_some(InclRange::new(start, end))
+    };
+
+    for (index, line) in lines.iter().enumerate() {
+        let content = line.trim_start();
+        if content.is_empty() || content.starts_with('#') {
+            continue;
+        }
+
+        let leading = &line[..line.len() - content.len()];
+        if leading.chars().any(|c| c != unit) || leading.len() % width != 0 {
+            diagnostics.push(Diagnostic::InconsistentIndent { line: index });
+        }
+
+        let indent = leading.len();
+        let opened = opener(content);
+
+        while let Some(&(start, open_indent, open)) = stack.last() {
+            let inside = open_indent < indent
+                || (open == Opener::Key && open_indent == indent && opened == Some(Opener::Item));
+
+            if inside {
+                break;
+            }
+
+            ranges.extend(close((start, open_indent, open), last));
+            stack.pop();
+        }
+
+        if let Some(opened) = opened {
+            stack.push((index, indent, opened));
+        }
+
+        last = index;
+    }
+
+    while let Some(open) = stack.pop() {
+        ranges.extend(close(open, last));
+    }
+
+    //  A key with nothing nested under it is just a value
+    ranges.retain(|range| range.lower != range.upper || opener(lines[range.lower].trim_start()) == Some(Opener::Item));
+
+    Divided {
+        ranges: ranges.into(),
+        keys: vec![],
+        kinds: vec![],
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const LEVEL: &str = indoc!(
+        "config:
+          speed: 1.5
+        notes:
+          - type: press
+            lane: 0
+
+          - type: hold  # a comment
+            lane: 1
+            points:
+            - time: 0
+            - time: 4
+        tail: x"
+    );
+
+    fn divided(block: &str, level: Option<usize>) -> Vec<(usize, usize)> {
+        let lines = LEVEL.lines().collect::<Vec<_>>();
+        blocks(&Regex::new(block).unwrap(), level, &lines)
+            .ranges
+            .iter()
+            .map(|range| (range.lower, range.upper))
+            .collect()
+    }
+
+    #[test]
+    fn indent_detection() {
+        assert_eq!(indent_width(&LEVEL.lines().collect::<Vec<_>>()), (' ', 2));
+        assert_eq!(indent_width(&["a:", "\tb:", "\t\tc"]), ('\t', 1));
+    }
+
+    #[test]
+    fn sequence_items() {
+        assert_eq!(divided(r"^\s*- type", None), vec![(3, 4), (6, 10)]);
+        assert_eq!(divided(r"^\s*- ", Some(2)), vec![(9, 9), (10, 10)]);
+    }
+
+    #[test]
+    fn mappings() {
+        assert_eq!(divided(r"^\w+:", None), vec![(0, 1), (2, 10)]);
+        assert_eq!(divided(r"points:", None), vec![(8, 10)]);
+    }
+
+    #[test]
+    fn mixed_indents() {
+        let lines = ["a:", "  - b", "\t- c"];
+        let divided = blocks(&Regex::new(".*").unwrap(), None, &lines);
+        assert_eq!(divided.diagnostics, vec![Diagnostic::InconsistentIndent { line: 2 }]);
+    }
+
+    #[test]
+    fn misindented_line() {
+        let lines = ["a:", "  b:", "    c: 1", "   d: 2", "  e:", "    f: 3"];
+        assert_eq!(indent_width(&lines), (' ', 2));
+
+        let divided = blocks(&Regex::new(r"^\s*\w+:$").unwrap(), Some(1), &lines);
+        assert_eq!(divided.diagnostics, vec![Diagnostic::InconsistentIndent { line: 3 }]);
+        assert_eq!(divided.ranges, [(1, 3), (4, 5)].into());
+    }
+}
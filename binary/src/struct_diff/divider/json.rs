@@ -0,0 +1,314 @@
+use super::super::range_vec::InclRange;
+
+//  Just enough of JSON to know where every value starts and ends.
+//  Scalars aren't interpreted since only their extent matters.
+#[derive(Debug)]
+enum Value {
+    Object(Vec<(String, Node)>),
+    Array(Vec<Node>),
+    Scalar,
+}
+
+#[derive(Debug)]
+struct Node {
+    start: usize,
+    end: usize,
+    value: Value,
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+type Parsed<T> = Result<T, (usize, String)>;
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.text.len() && self.text[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Parsed<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(found) if found == byte => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(found) => Err((self.pos, format!("expected '{}' but found '{}'", byte as char, found as char))),
+            None => Err((self.pos, format!("expected '{}' but the text ended", byte as char))),
+        }
+    }
+
+    fn string(&mut self) -> Parsed<String> {
+        self.expect(b'"')?;
+        let start = self.pos;
+
+        while let Some(byte) = self.peek() {
+            match byte {
+                b'\\' => self.pos += 2,
+                b'"' => {
+                    let contents = String::from_utf8_lossy(&self.text[start..self.pos]).into_owned();
+                    self.pos += 1;
+                    return Ok(contents);
+                }
+                _ => self.pos += 1,
+            }
+        }
+
+        Err((start - 1, "string is never closed".to_string()))
+    }
+
+    //  Trailing commas are let through since hand edited maps often have them
+    fn sequence<T, F>(&mut self, close: u8, mut item: F) -> Parsed<Vec<T>>
+    where
+        F: FnMut(&mut Self) -> Parsed<T>
+    {
+        let mut items = vec![];
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some(close) {
+                self.pos += 1;
+                return Ok(items);
+            }
+
+            items.push(item(self)?);
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(found) if found == close => {}
+                Some(found) => return Err((self.pos, format!("expected ',' or '{}' but found '{}'", close as char, found as char))),
+                None => return Err((self.pos, format!("expected '{}' but the text ended", close as char))),
+            }
+        }
+    }
+
+    fn node(&mut self) -> Parsed<Node> {
+        self.skip_whitespace();
+        let start = self.pos;
+
+        let value = match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                Value::Object(self.sequence(b'}', |parser| {
+                    let key = parser.string()?;
+                    parser.expect(b':')?;
+                    Ok((key, parser.node()?))
+                })?)
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                Value::Array(self.sequence(b']', Self::node)?)
+            }
+            Some(b'"') => {
+                self.string()?;
+                Value::Scalar
+            }
+            Some(_) => {
+                while let Some(byte) = self.peek() {
+                    if byte.is_ascii_whitespace() || matches!(byte, b',' | b']' | b'}' | b':') {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+
+                if self.pos == start {
+                    return Err((start, format!("unexpected '{}'", self.text[start] as char)));
+                }
+
+                Value::Scalar
+            }
+            None => return Err((start, "expected a value but the text ended".to_string())),
+        };
+
+        Ok(Node { start, end: self.pos, value })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Child(String),
+    Index(usize),
+    Wildcard,
+    Descendants(String),
+}
+
+//  A JSONPath subset: $, .name, ['name'], [n], [*], .* and ..name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector(Vec<Step>);
+
+impl Selector {
+    pub fn parse(path: &str) -> Option<Self> {
+        let mut rest = path.trim().strip_prefix('$')?;
+        let mut steps = vec![];
+
+        let name_end = |s: &str| s
+            .find(['.', '['])
+            .unwrap_or(s.len());
+
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("..") {
+                let end = name_end(after);
+                if end == 0 {
+                    return None;
+                }
+                steps.push(Step::Descendants(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('.') {
+                let end = name_end(after);
+                steps.push(match &after[..end] {
+                    "" => return None,
+                    "*" => Step::Wildcard,
+                    name => Step::Child(name.to_string()),
+                });
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']')?;
+                let inner = after[..end].trim();
+                steps.push(match inner {
+                    "*" => Step::Wildcard,
+                    _ if inner.starts_with(['\'', '"']) => {
+                        let quote = &inner[..1];
+                        let name = inner[1..].strip_suffix(quote)?;
+                        Step::Child(name.to_string())
+                    }
+                    _ => Step::Index(inner.parse().ok()?),
+                });
+                rest = &after[end + 1..];
+            } else {
+                return None;
+            }
+        }
+
+        Some(Self(steps))
+    }
+
+    fn descendants<'a>(node: &'a Node, name: &str, found: &mut Vec<&'a Node>) {
+        match &node.value {
+            Value::Object(members) => members.iter().for_each(|(key, child)| {
+                if key == name {
+                    found.push(child);
+                }
+                Self::descendants(child, name, found);
+            }),
+            Value::Array(items) => items
+                .iter()
+                .for_each(|child| Self::descendants(child, name, found)),
+            Value::Scalar => {}
+        }
+    }
+
+    fn select<'a>(&self, root: &'a Node) -> Vec<&'a Node> {
+        self.0.iter().fold(vec![root], |nodes, step| nodes
+            .into_iter()
+            .flat_map(|node| match (step, &node.value) {
+                (Step::Child(name), Value::Object(members)) => members
+                    .iter()
+                    .filter(|(key, _)| key == name)
+                    .map(|(_, child)| child)
+                    .collect(),
+                (Step::Index(i), Value::Array(items)) => items.get(*i).into_iter().collect(),
+                (Step::Wildcard, Value::Object(members)) => members.iter().map(|(_, child)| child).collect(),
+                (Step::Wildcard, Value::Array(items)) => items.iter().collect(),
+                (Step::Descendants(name), _) => {
+                    let mut found = vec![];
+                    Self::descendants(node, name, &mut found);
+                    found
+                }
+                _ => vec![],
+            })
+            .collect()
+        )
+    }
+
+    //  Byte spans of every selected value, or where and why the text isn't JSON
+    pub fn spans(&self, text: &str) -> Result<Vec<InclRange>, (usize, String)> {
+        let mut parser = Parser { text: text.as_bytes(), pos: 0 };
+        let root = parser.node()?;
+
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err((parser.pos, "unexpected text after the document".to_string()));
+        }
+
+        Ok(self
+            .select(&root)
+            .into_iter()
+            .map(|node| InclRange::new(node.start, node.end - 1))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const LEVEL: &str = indoc!(
+        r#"{
+            "config": { "speed": 1.5 },
+            "objs": [
+                { "type": "press", "lane": 0 },
+                {
+                    "type": "hold",
+                    "lane": 1,
+                    "tag": "}]"
+                },
+            ]
+        }"#
+    );
+
+    fn selected(path: &str) -> Vec<&'static str> {
+        Selector::parse(path)
+            .unwrap()
+            .spans(LEVEL)
+            .unwrap()
+            .into_iter()
+            .map(|span| &LEVEL[span.lower..=span.upper])
+            .collect()
+    }
+
+    #[test]
+    fn selector_parse() {
+        assert_eq!(
+            Selector::parse("$.objs[*]['lane']..x[2].*"),
+            Some(Selector(vec![
+                Step::Child("objs".to_string()),
+                Step::Wildcard,
+                Step::Child("lane".to_string()),
+                Step::Descendants("x".to_string()),
+                Step::Index(2),
+                Step::Wildcard,
+            ]))
+        );
+        assert_eq!(Selector::parse("objs"), None);
+        assert_eq!(Selector::parse("$.objs[x]"), None);
+        assert_eq!(Selector::parse("$['objs]"), None);
+        assert_eq!(Selector::parse("$['objs\"]"), None);
+        assert_eq!(Selector::parse("$['é]"), None);
+        assert_eq!(Selector::parse("$[']"), None);
+    }
+
+    #[test]
+    fn selector_spans() {
+        assert_eq!(selected("$.objs[0]"), vec![r#"{ "type": "press", "lane": 0 }"#]);
+        assert_eq!(selected("$.objs[*].lane"), vec!["0", "1"]);
+        assert_eq!(selected("$..speed"), vec!["1.5"]);
+        assert_eq!(selected("$.objs[*]")[1].lines().count(), 5);
+    }
+
+    #[test]
+    fn malformed() {
+        let selector = Selector::parse("$.objs").unwrap();
+        assert_eq!(selector.spans(r#"{"objs": [1, 2}"#).unwrap_err().0, 14);
+        assert!(selector.spans(r#"{"objs": "#).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+use regex::Regex;
+
+use super::super::range_vec::InclRange;
+use super::{Diagnostic, Divided};
+
+//  Column names of a table and the lines they apply to,
+//  from the line that starts the table to its last row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub region: InclRange,
+    pub columns: Vec<String>,
+    pub separator: String,
+}
+
+impl Header {
+    pub fn cell(&self, line: &str, column: &str) -> Option<String> {
+        let index = self.columns.iter().position(|name| name == column)?;
+        split_row(line, &self.separator)
+            .into_iter()
+            .nth(index)
+            .filter(|cell| !cell.is_empty())
+    }
+}
+
+//  Cells are trimmed and may be double quoted to hold the separator, "" being a quote
+pub fn split_row(line: &str, separator: &str) -> Vec<String> {
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut rest = line;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '"' && quoted && rest[1..].starts_with('"') {
+            cell.push('"');
+            rest = &rest[2..];
+        } else if c == '"' && (quoted || cell.trim().is_empty()) {
+            quoted = !quoted;
+            rest = &rest[1..];
+        } else if !quoted && !separator.is_empty() && rest.starts_with(separator) {
+            cells.push(cell.trim().to_string());
+            cell.clear();
+            rest = &rest[separator.len()..];
+        } else {
+            cell.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+//  A region is every line after one matching start up to the next blank line
+fn regions(start: &Regex, lines: &[&str]) -> Vec<InclRange> {
+    //  rows only, the start line isn't part of it
+    lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| start.is_match(line))
+        .filter_map(|(index, _)| {
+            let rows = lines[index + 1..]
+                .iter()
+                .take_while(|line| !line.trim().is_empty())
+                .count();
+
+            (0 < rows).then(|| InclRange::new(index + 1, index + rows))
+        })
+        .collect()
+}
+
+pub fn headers(start: &Regex, separator: &str, header: bool, lines: &[&str]) -> Vec<Header> {
+    if !header {
+        return vec![];
+    }
+
+    regions(start, lines)
+        .into_iter()
+        .map(|rows| Header {
+            region: InclRange::new(rows.lower - 1, rows.upper),
+            columns: split_row(lines[rows.lower], separator),
+            separator: separator.to_string()
+        })
+        .collect()
+}
+
+pub fn rows(start: &Regex, separator: &str, header: bool, lines: &[&str]) -> Divided {
+    let mut ranges = vec![];
+    let mut diagnostics = vec![];
+
+    for region in regions(start, lines) {
+        let first = region.lower + header as usize;
+        let width = split_row(lines[region.lower], separator).len();
+
+        for (index, line) in lines.iter().enumerate().take(region.upper + 1).skip(first) {
+            if split_row(line, separator).len() != width {
+                diagnostics.push(Diagnostic::Unparsable {
+                    line: index,
+                    reason: format!("row does not have {} cells", width)
+                });
+            }
+            ranges.push(InclRange::new(index, index));
+        }
+    }
+
+    Divided {
+        ranges: ranges.into(),
+        keys: vec![],
+        kinds: vec![],
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const CHART: &str = indoc!(
+        "[config]
+        a = 0
+
+        [notes]
+        lane, time, label
+        0, 0, \"a, b\"
+        1, 4, \"say \"\"hi\"\"\"
+        2, 8
+
+        [other]"
+    );
+
+    #[test]
+    fn split() {
+        assert_eq!(split_row(r#" 1, "a, b" ,c"#, ","), vec!["1", "a, b", "c"]);
+        assert_eq!(split_row("1\t2\t", "\t"), vec!["1", "2", ""]);
+        assert_eq!(split_row(r#""say ""hi""""#, ","), vec![r#"say "hi""#]);
+    }
+
+    #[test]
+    fn table_rows() {
+        let lines = CHART.lines().collect::<Vec<_>>();
+        let start = Regex::new(r"\[notes\]").unwrap();
+        let divided = rows(&start, ",", true, &lines);
+
+        assert_eq!(divided.ranges, [(5, 5), (6, 6), (7, 7)].into());
+        assert_eq!(divided.diagnostics.len(), 1);
+        assert_eq!(divided.diagnostics[0].line(), 7);
+
+        let header = &headers(&start, ",", true, &lines)[0];
+        assert_eq!(header.region, InclRange::new(3, 7));
+        assert_eq!(header.cell(lines[5], "label").as_deref(), Some("a, b"));
+        assert_eq!(header.cell(lines[6], "time").as_deref(), Some("4"));
+        assert_eq!(header.cell(lines[7], "label"), None);
+    }
+}
@@ -0,0 +1,225 @@
+use super::super::range_vec::InclRange;
+use super::{line_index, Diagnostic, Divided};
+
+//  Elements are picked by name, by the names of their closest ancestors (Chart/Note)
+//  or by their full path from a root when it starts with a slash (/Level/Chart/Note).
+//  A * stands in for any one name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlPath {
+    absolute: bool,
+    names: Vec<String>,
+}
+
+impl XmlPath {
+    pub fn parse(path: &str) -> Option<Self> {
+        let path = path.trim();
+        let absolute = path.starts_with('/');
+        let names = path
+            .trim_start_matches('/')
+            .split('/')
+            .map(|name| name.trim().to_string())
+            .collect::<Vec<_>>();
+
+        names
+            .iter()
+            .all(|name| !name.is_empty())
+            .then_some(Self { absolute, names })
+    }
+
+    fn matches(&self, path: &[&str]) -> bool {
+        let fits = path.len() == self.names.len() || (!self.absolute && path.len() > self.names.len());
+
+        fits && path[path.len() - self.names.len()..]
+            .iter()
+            .zip(self.names.iter())
+            .all(|(found, name)| name == "*" || found == name)
+    }
+
+    pub fn divide(&self, text: &str) -> Divided {
+        let line_of = line_index(text);
+        let bytes = text.as_bytes();
+        let find = |from: usize, pattern: &str| text[from..]
+            .find(pattern)
+            .map(|i| from + i + pattern.len());
+
+        let mut ranges = vec![];
+        let mut kinds = vec![];
+        let mut diagnostics = vec![];
+        let mut stack: Vec<(&str, usize)> = vec![];
+        let mut pos = 0;
+
+        let mut push = |stack: &[(&str, usize)], name: &str, start: usize, end: usize| {
+            let path = stack
+                .iter()
+                .map(|(name, _)| *name)
+                .chain([name])
+                .collect::<Vec<_>>();
+
+            if self.matches(&path) {
+                ranges.push(InclRange::new(start, end - 1));
+                kinds.push((InclRange::new(start, end - 1), name.to_string()));
+            }
+        };
+
+        while let Some(offset) = text[pos..].find('<') {
+            let start = pos + offset;
+            let rest = &text[start..];
+
+            let skipped = if rest.starts_with("<!--") {
+                Some(("-->", "comment"))
+            } else if rest.starts_with("<![CDATA[") {
+                Some(("]]>", "CDATA section"))
+            } else if rest.starts_with("<?") {
+                Some(("?>", "processing instruction"))
+            } else if rest.starts_with("<!") {
+                Some((">", "declaration"))
+            } else {
+                None
+            };
+
+            if let Some((closer, what)) = skipped {
+                match find(start, closer) {
+                    Some(end) => pos = end,
+                    None => {
+                        diagnostics.push(Diagnostic::Unparsable {
+                            line: line_of(start),
+                            reason: format!("{} is never closed", what)
+                        });
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            //  Attribute values may hold a > and span lines so quotes have to be followed
+            let mut end = start + 1;
+            let mut quote = None;
+            while end < bytes.len() {
+                match (quote, bytes[end]) {
+                    (None, b'>') => break,
+                    (None, byte @ (b'"' | b'\'')) => quote = Some(byte),
+                    (Some(open), byte) if open == byte => quote = None,
+                    _ => {}
+                }
+                end += 1;
+            }
+
+            if end == bytes.len() {
+                diagnostics.push(Diagnostic::Unparsable {
+                    line: line_of(start),
+                    reason: "tag is never closed".to_string()
+                });
+                break;
+            }
+
+            let tag = &text[start + 1..end];
+            let name = tag
+                .trim_start_matches('/')
+                .split(|c: char| c.is_whitespace() || c == '/')
+                .next()
+                .unwrap_or("");
+
+            pos = end + 1;
+
+            if name.is_empty() {
+                diagnostics.push(Diagnostic::Unparsable {
+                    line: line_of(start),
+                    reason: "tag has no name".to_string()
+                });
+            } else if tag.starts_with('/') {
+                match stack.iter().rposition(|(open, _)| *open == name) {
+                    Some(index) => {
+                        //  Anything opened inside and never closed ends where its parent does
+                        while stack.len() > index + 1 {
+                            let (inner, inner_start) = stack.pop().unwrap_or_default();
+                            diagnostics.push(Diagnostic::Unclosed { line: line_of(inner_start) });
+                            push(&stack, inner, inner_start, start);
+                        }
+
+                        let (_, open_start) = stack.pop().unwrap_or_default();
+                        push(&stack, name, open_start, pos);
+                    }
+                    None => diagnostics.push(Diagnostic::StrayClose { line: line_of(start) }),
+                }
+            } else if tag.ends_with('/') {
+                push(&stack, name, start, pos);
+            } else {
+                stack.push((name, start));
+            }
+        }
+
+        while let Some((name, start)) = stack.pop() {
+            diagnostics.push(Diagnostic::Unclosed { line: line_of(start) });
+            push(&stack, name, start, text.len());
+        }
+
+        Divided {
+            ranges: ranges.into(),
+            keys: vec![],
+            kinds,
+            diagnostics
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    const CHART: &str = indoc!(
+        r#"<?xml version="1.0"?>
+        <Level>
+            <!-- <Note lane="9"/> -->
+            <Chart>
+                <Note lane="0"/>
+                <Note
+                    lane="1"
+                    label="a > b"
+                />
+                <Note lane="2"><![CDATA[</Note>]]></Note>
+            </Chart>
+            <Note lane="3"></Note>
+        </Level>"#
+    );
+
+    fn selected(path: &str) -> Vec<&'static str> {
+        XmlPath::parse(path)
+            .unwrap()
+            .divide(CHART)
+            .ranges
+            .iter()
+            .map(|span| &CHART[span.lower..=span.upper])
+            .collect()
+    }
+
+    #[test]
+    fn xml_path_parse() {
+        assert_eq!(
+            XmlPath::parse("/Level/*"),
+            Some(XmlPath { absolute: true, names: vec!["Level".to_string(), "*".to_string()] })
+        );
+        assert_eq!(XmlPath::parse("Chart//Note"), None);
+    }
+
+    #[test]
+    fn xml_elements() {
+        assert_eq!(selected("Note").len(), 4);
+        assert_eq!(selected("Chart/Note")[0], r#"<Note lane="0"/>"#);
+        assert_eq!(selected("Chart/Note")[1].lines().count(), 4);
+        assert_eq!(selected("Chart/Note")[2], r#"<Note lane="2"><![CDATA[</Note>]]></Note>"#);
+        assert_eq!(selected("/Level/Note"), vec![r#"<Note lane="3"></Note>"#]);
+    }
+
+    #[test]
+    fn xml_diagnostics() {
+        let path = XmlPath::parse("Note").unwrap();
+        let divided = path.divide("<Chart>\n<Note>\n</Chart>\n</Note>");
+
+        assert_eq!(divided.ranges.len(), 1);
+        assert_eq!(
+            divided.diagnostics,
+            vec![Diagnostic::Unclosed { line: 1 }, Diagnostic::StrayClose { line: 3 }]
+        );
+    }
+}
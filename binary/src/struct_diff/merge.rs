@@ -0,0 +1,654 @@
+use std::ops::Range;
+use regex::Regex;
+use itertools::Itertools;
+use super::{diff::*, structure::*};
+
+//  Lines wrapped around each conflicting object in a merged map
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Markers {
+    pub start: String,
+    pub middle: String,
+    pub end: String,
+}
+
+impl Default for Markers {
+    fn default() -> Self {
+        Self {
+            start: "<<<<<<< folded".to_string(),
+            middle: "=======".to_string(),
+            end: ">>>>>>> modded".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Keep {
+    Left,
+    Right,
+    Both,
+}
+
+//  Settles conflicts over objects with a value matching a pattern for the key labelled key,
+//  e.g. lane for "lane: [0-9]*" (see Key::label)
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub key: String,
+    pub matches: Regex,
+    pub keep: Keep,
+}
+
+//  How conflicts are settled without asking anyone. The first rule matching any version
+//  of the conflicting objects decides, otherwise decides the rest, and conflicts left
+//  undecided get marked.
+#[derive(Debug, Clone, Default)]
+pub struct Resolution {
+    pub rules: Vec<Rule>,
+    pub otherwise: Option<Keep>,
+}
+
+impl Resolution {
+    pub fn keep(keep: Keep) -> Self {
+        Self { rules: vec![], otherwise: Some(keep) }
+    }
+
+    fn decide(&self, config: &Config, keys: &[Vec<String>]) -> Option<Keep> {
+        self.rules
+            .iter()
+            .find(|rule| config.keys
+                .iter()
+                .position(|key| key.label() == rule.key)
+                .is_some_and(|i| keys
+                    .iter()
+                    .filter_map(|key| key.get(i))
+                    .any(|value| rule.matches.is_match(value))
+                )
+            )
+            .map(|rule| rule.keep)
+            .or(self.otherwise)
+    }
+}
+
+//  Which side got its way in a conflict settled by a Resolution, and against which mod.
+//  The mod's id is only known once it's folded, until then it's empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved {
+    pub keys: Vec<Vec<String>>,
+    pub kept: Keep,
+    pub mod_id: String,
+    pub comment: String,
+}
+
+//  Original units from..to replaced by a mod with lines, which start at patched units at
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Hunk {
+    from: usize,
+    to: usize,
+    at: usize,
+    lines: Vec<String>,
+}
+
+//  What a mod does to the original, pieced together from the units it keeps
+fn hunks(modifications: &StructDiff, original: usize, patched: &[String]) -> Vec<Hunk> {
+    let removed = |i: usize| modifications.removed.binary_search(&i).is_ok();
+    let added = |j: usize| modifications.added.binary_search(&j).is_ok();
+
+    let mut hunks = vec![];
+    let (mut i, mut j) = (0, 0);
+    loop {
+        while i < original && j < patched.len() && !removed(i) && !added(j) {
+            i += 1;
+            j += 1;
+        }
+
+        let (from, at) = (i, j);
+        while i < original && removed(i) {
+            i += 1;
+        }
+        while j < patched.len() && added(j) {
+            j += 1;
+        }
+
+        if (from, at) == (i, j) {
+            break;
+        }
+
+        hunks.push(Hunk { from, to: i, at, lines: patched[at..j].to_vec() });
+    }
+
+    hunks
+}
+
+impl Hunk {
+    //  Where a diff puts an insertion among identical lines is arbitrary, so slide it out of
+    //  objects it was put inside of if it fits just as well between them
+    fn slid(self, objs: &[Obj], original: &[String]) -> Self {
+        let inside = |at: usize| objs
+            .iter()
+            .any(|obj| obj.range.lower < at && at <= obj.range.upper);
+
+        if self.from != self.to || self.lines.is_empty() || !inside(self.from) {
+            return self;
+        }
+
+        let mut up = self.clone();
+        while inside(up.from) && 0 < up.from && up.lines.last() == Some(&original[up.from - 1]) {
+            up.lines.rotate_right(1);
+            up.from -= 1;
+            up.to -= 1;
+            up.at -= 1;
+        }
+
+        let mut down = self.clone();
+        while inside(down.from) && down.from < original.len() && down.lines.first() == Some(&original[down.from]) {
+            down.lines.rotate_left(1);
+            down.from += 1;
+            down.to += 1;
+            down.at += 1;
+        }
+
+        [up, down].into_iter().find(|hunk| !inside(hunk.from)).unwrap_or(self)
+    }
+
+    //  A hunk rewriting as many lines as it replaces is taken as a change to each of
+    //  them, so the fields it leaves alone or changes the same as the other side don't
+    //  count against it
+    fn fields(&self, original: &[String]) -> Vec<Hunk> {
+        if self.to - self.from != self.lines.len() {
+            return vec![self.clone()];
+        }
+
+        self.lines
+            .iter()
+            .enumerate()
+            .filter(|(i, line)| original[self.from + i] != **line)
+            .map(|(i, line)| Hunk { from: self.from + i, to: self.from + i + 1, at: self.at + i, lines: vec![line.clone()] })
+            .collect()
+    }
+}
+
+//  Whether two changes to the original, as from..to, need to be looked at together.
+//  Insertions only touch what they're inserted into, or insertions at the same place.
+fn touches(span: (usize, usize), other: (usize, usize)) -> bool {
+    let inside = |at: usize, (from, to): (usize, usize)| from < at && at < to;
+    match (span.0 == span.1, other.0 == other.1) {
+        (true, true) => span.0 == other.0,
+        (true, false) => inside(span.0, other),
+        (false, true) => inside(other.0, span),
+        (false, false) => span.0 < other.1 && other.0 < span.1,
+    }
+}
+
+//  Original units from..to with hunks that lie within them applied
+fn apply(hunks: &[&Hunk], (from, to): (usize, usize), original: &[String]) -> Vec<String> {
+    let mut lines = vec![];
+    let mut at = from;
+
+    for hunk in hunks {
+        lines.extend_from_slice(&original[at..hunk.from]);
+        lines.extend(hunk.lines.iter().cloned());
+        at = hunk.to;
+    }
+
+    lines.extend_from_slice(&original[at..to]);
+    lines
+}
+
+//  Hunks that touch, each tagged with whether it's from the left, and the span they cover
+type Group<'a> = ((usize, usize), Vec<(bool, &'a Hunk)>);
+
+//  Part of the original changed by either mod, widened to whole objects
+#[derive(Debug, Clone)]
+struct Region {
+    from: usize,
+    to: usize,
+    left: Vec<Hunk>,
+    right: Vec<Hunk>,
+}
+
+impl Region {
+    fn side(&self, hunks: &[Hunk], original: &[String]) -> Option<Vec<String>> {
+        (!hunks.is_empty()).then(|| apply(&hunks.iter().collect::<Vec<_>>(), (self.from, self.to), original))
+    }
+
+    //  Both sides' changes to the region at once, as long as they don't change the same
+    //  lines differently. Lines are where an object keeps its fields, so mods editing
+    //  different fields of an object both get their way, and mods making the same edit
+    //  make it once.
+    fn combined(&self, original: &[String]) -> Option<Vec<String>> {
+        let fields = |hunks: &[Hunk]| hunks
+            .iter()
+            .flat_map(|hunk| hunk.fields(original))
+            .collect::<Vec<_>>();
+
+        let (left, right) = (fields(&self.left), fields(&self.right));
+        let mut hunks = left
+            .iter()
+            .map(|hunk| (true, hunk))
+            .chain(right.iter().map(|hunk| (false, hunk)))
+            .collect::<Vec<_>>();
+
+        hunks.sort_by_key(|(_, hunk)| (hunk.from, hunk.to));
+
+        let mut groups: Vec<Group> = vec![];
+        for (is_left, hunk) in hunks {
+            match groups.last_mut() {
+                Some((span, group)) if touches(*span, (hunk.from, hunk.to)) => {
+                    *span = (span.0.min(hunk.from), span.1.max(hunk.to));
+                    group.push((is_left, hunk));
+                }
+                _ => groups.push(((hunk.from, hunk.to), vec![(is_left, hunk)])),
+            }
+        }
+
+        let mut lines = vec![];
+        let mut at = self.from;
+
+        for (span, group) in groups {
+            let side = |left: bool| group
+                .iter()
+                .filter(|(is_left, _)| *is_left == left)
+                .map(|(_, hunk)| *hunk)
+                .collect::<Vec<_>>();
+
+            let (left, right) = (side(true), side(false));
+            let (left_lines, right_lines) = (apply(&left, span, original), apply(&right, span, original));
+
+            lines.extend_from_slice(&original[at..span.0]);
+            match (left.is_empty(), right.is_empty()) {
+                (false, false) if left_lines != right_lines => return None,
+                (false, _) => lines.extend(left_lines),
+                (true, _) => lines.extend(right_lines),
+            }
+            at = span.1;
+        }
+
+        lines.extend_from_slice(&original[at..self.to]);
+        Some(lines)
+    }
+}
+
+//  Where a conflicting object is in the original and in each side once patched, as
+//  ranges of units, along with the kinds and keys of every version of it and the text
+//  each side has for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub original: Range<usize>,
+    pub left: Range<usize>,
+    pub right: Range<usize>,
+    pub kinds: Vec<String>,
+    pub keys: Vec<Vec<String>>,
+    pub left_text: String,
+    pub right_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+    pub units: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+    pub resolved: Vec<Resolved>,
+}
+
+impl Structure {
+    //  Widens a hunk to the objects it touches, so changes to the same object end up together
+    fn widen(objs: &[Obj], hunk: &Hunk) -> (usize, usize) {
+        objs.iter()
+            .filter(|obj| match hunk.from == hunk.to {
+                true => obj.range.lower < hunk.from && hunk.from <= obj.range.upper,
+                false => obj.range.lower < hunk.to && hunk.from <= obj.range.upper,
+            })
+            .fold((hunk.from, hunk.to), |(from, to), obj| {
+                (from.min(obj.range.lower), to.max(obj.range.upper + 1))
+            })
+    }
+
+    fn regions(&self, objs: &[Obj], left: &StructDiff, right: &StructDiff) -> Vec<Region> {
+        let original = &self.contents;
+        let [left_hunks, right_hunks] = [left, right]
+            .map(|modifications| hunks(modifications, original.len(), &modifications.patch_units(original))
+                .into_iter()
+                .map(|hunk| hunk.slid(objs, original))
+                .collect::<Vec<_>>()
+            );
+
+        let mut tagged = left_hunks
+            .into_iter()
+            .map(|hunk| (true, hunk))
+            .chain(right_hunks.into_iter().map(|hunk| (false, hunk)))
+            .collect::<Vec<_>>();
+
+        tagged.sort_by_key(|(_, hunk)| (hunk.from, hunk.to));
+
+        let mut regions: Vec<Region> = vec![];
+        for (is_left, hunk) in tagged {
+            let (from, to) = Self::widen(objs, &hunk);
+            let region = match regions.last_mut() {
+                Some(region) if touches((region.from, region.to), (from, to)) => region,
+                _ => {
+                    regions.push(Region { from, to, left: vec![], right: vec![] });
+                    regions.last_mut().unwrap()
+                }
+            };
+
+            region.from = region.from.min(from);
+            region.to = region.to.max(to);
+            match is_left {
+                true => region.left.push(hunk),
+                false => region.right.push(hunk),
+            }
+        }
+
+        regions
+    }
+
+    //  Applies both mods to the original. Objects both change are merged field by field
+    //  where possible. Where they can't be, or where objects changed by either collide by
+    //  key with a different object changed by the other, both versions are kept between
+    //  the config's markers, with the original standing in for a side that left it alone,
+    //  unless the resolution settles them.
+    pub fn merge(&self, left: &StructDiff, right: &StructDiff, resolution: &Resolution) -> Merged {
+        let original = &self.contents;
+        let objs = self.index().objs;
+        let regions = self.regions(&objs, left, right);
+        let [left_objs, right_objs] = [left, right].map(|modifications| self.patched(modifications).index().objs);
+
+        //  Each side of each region, with where it starts once patched
+        let mut shifts = (0isize, 0isize);
+        let sides = regions
+            .iter()
+            .map(|region| {
+                let unchanged = || original[region.from..region.to].to_vec();
+                let left_lines = region.side(&region.left, original);
+                let right_lines = region.side(&region.right, original);
+
+                let left_at = (region.from as isize + shifts.0) as usize;
+                let right_at = (region.from as isize + shifts.1) as usize;
+                let (left_lines, right_lines) = (
+                    left_lines.unwrap_or_else(unchanged),
+                    right_lines.unwrap_or_else(unchanged)
+                );
+
+                shifts.0 += left_lines.len() as isize - (region.to - region.from) as isize;
+                shifts.1 += right_lines.len() as isize - (region.to - region.from) as isize;
+                (left_at..left_at + left_lines.len(), left_lines, right_at..right_at + right_lines.len(), right_lines)
+            })
+            .collect::<Vec<_>>();
+
+        //  A collision between the two versions of the same object was already looked at
+        //  field by field, any other means the regions involved conflict
+        let mut colliding = vec![false; regions.len()];
+        for (left_lines, right_lines) in self.collisions(left, right) {
+            let hit = sides
+                .iter()
+                .enumerate()
+                .filter(|(_, (left_range, _, right_range, _))| {
+                    left_lines.iter().any(|line| left_range.contains(line))
+                        || right_lines.iter().any(|line| right_range.contains(line))
+                })
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            let same_object = matches!(hit.as_slice(), [i] if !regions[*i].left.is_empty() && !regions[*i].right.is_empty());
+            if !same_object {
+                hit.into_iter().for_each(|i| colliding[i] = true);
+            }
+        }
+
+        let markers = &self.config.markers;
+        let marker = |marker: &String| match left.spans {
+            Some(_) => format!("\n{}\n", marker),
+            None => marker.clone(),
+        };
+
+        let within = |objs: &'_ [Obj], range: &Range<usize>| objs
+            .iter()
+            .filter(|obj| obj.range.lower < range.end && range.start <= obj.range.upper)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut merged = Merged { units: vec![], conflicts: vec![], resolved: vec![] };
+        let mut at = 0;
+
+        for ((region, (left_range, left_lines, right_range, right_lines)), colliding) in regions.iter().zip(sides).zip(colliding) {
+            merged.units.extend_from_slice(&original[at..region.from]);
+            at = region.to;
+
+            let combined = match (region.left.is_empty(), region.right.is_empty(), colliding) {
+                (_, _, true) => None,
+                (false, false, false) => region.combined(original),
+                (false, true, false) => Some(left_lines.clone()),
+                (true, _, false) => Some(right_lines.clone()),
+            };
+
+            let Some(lines) = combined else {
+                let versions = within(&objs, &(region.from..region.to))
+                    .into_iter()
+                    .chain(within(&left_objs, &left_range))
+                    .chain(within(&right_objs, &right_range))
+                    .collect::<Vec<_>>();
+
+                let conflict = Conflict {
+                    original: region.from..region.to,
+                    left: left_range,
+                    right: right_range,
+                    kinds: versions.iter().filter_map(|obj| obj.kind.clone()).unique().collect(),
+                    keys: versions.into_iter().map(|obj| obj.key).unique().collect(),
+                    left_text: left.join(&left_lines),
+                    right_text: right.join(&right_lines),
+                };
+
+                match resolution.decide(&self.config, &conflict.keys) {
+                    Some(kept) => {
+                        match kept {
+                            Keep::Left => merged.units.extend(left_lines),
+                            Keep::Right => merged.units.extend(right_lines),
+                            Keep::Both => merged.units.extend(left_lines.into_iter().chain(right_lines)),
+                        }
+
+                        merged.resolved.push(Resolved { keys: conflict.keys, kept, mod_id: String::new(), comment: right.comment.clone() });
+                    }
+                    None => {
+                        merged.conflicts.push(conflict);
+                        merged.units.push(marker(&markers.start));
+                        merged.units.extend(left_lines);
+                        merged.units.push(marker(&markers.middle));
+                        merged.units.extend(right_lines);
+                        merged.units.push(marker(&markers.end));
+                    }
+                }
+                continue;
+            };
+
+            merged.units.extend(lines);
+        }
+
+        merged.units.extend_from_slice(&original[at..]);
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::*;
+    use indoc::indoc;
+
+    const ORIGINAL: &str = indoc!(
+        "objs: {
+            press: {
+                lane: 0,
+                time: 0,
+                color: 0xFF0000,
+            },
+            hold: {
+                lane: 1,
+                time: 0,
+                color: 0x00FF00,
+            },
+        }"
+    );
+
+    fn map() -> Structure {
+        let config_json = indoc!(r#"
+            {
+                "keys": [
+                    { "fuzzed": "lane: [0-9]*", "strict": "[0-9]+" },
+                    { "fuzzed": "time: [0-9]*", "strict": "[0-9]+" }
+                ],
+                "filter": { "prefix": "objs:", "open": "\\{", "close": "\\}" },
+                "expander": { "prefix": "(?P<kind>press|hold): \\{", "open": "\\{", "close": "\\}" },
+                "markers": { "start": "<<<", "middle": "===", "end": ">>>" }
+            }
+        "#);
+
+        Structure {
+            contents: ORIGINAL.lines().map(String::from).collect(),
+            config: serde_json::from_str::<ConfigDef>(config_json).unwrap().into(),
+        }
+    }
+
+    #[test]
+    fn hunks_of_diff() {
+        let modified = ORIGINAL.replace("time: 0,\n        color: 0x00FF00", "time: 4,\n        color: 0x00FF00");
+        let modifications = StructDiff::build_from(ORIGINAL, &modified, "");
+        let patched = modifications.patch_units(&map().contents);
+
+        assert_eq!(
+            hunks(&modifications, 12, &patched),
+            vec![Hunk { from: 8, to: 9, at: 8, lines: vec!["        time: 4,".to_string()] }]
+        );
+    }
+
+    #[test]
+    fn merge_clean() {
+        let left = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0xFF0000", "0xAA0000"), "");
+        let right = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00AA00"), "");
+
+        let merged = map().merge(&left, &right, &Resolution::default());
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.units.join("\n"), ORIGINAL.replace("0xFF0000", "0xAA0000").replace("0x00FF00", "0x00AA00"));
+    }
+
+    #[test]
+    fn merge_fields() {
+        let retimed = ORIGINAL.replace("time: 0,\n        color: 0x00FF00", "time: 4,\n        color: 0x00FF00");
+        let left = StructDiff::build_from(ORIGINAL, &retimed, "");
+        let right = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00AA00"), "");
+
+        let merged = map().merge(&left, &right, &Resolution::default());
+        assert!(merged.conflicts.is_empty());
+        assert_eq!(merged.units.join("\n"), retimed.replace("0x00FF00", "0x00AA00"));
+
+        let same = StructDiff::build_from(ORIGINAL, &retimed.replace("0x00FF00", "0x00AA00"), "");
+        assert!(map().merge(&left, &same, &Resolution::default()).conflicts.is_empty());
+
+        let other = StructDiff::build_from(ORIGINAL, &retimed.replace("time: 4", "time: 6"), "");
+        let conflicts = map().merge(&left, &other, &Resolution::default()).conflicts;
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!((conflicts[0].original.clone(), conflicts[0].left.clone(), conflicts[0].right.clone()), (6..11, 6..11, 6..11));
+        assert_eq!(conflicts[0].kinds, vec!["hold"]);
+        assert_eq!(conflicts[0].keys, [["1", "0"], ["1", "4"], ["1", "6"]].map(|key| key.map(String::from).to_vec()).to_vec());
+        assert!(conflicts[0].left_text.contains("time: 4") && conflicts[0].right_text.contains("time: 6"));
+    }
+
+    #[test]
+    fn merge_identical() {
+        let merged = |left: &str, right: &str| map().merge(
+            &StructDiff::build_from(ORIGINAL, left, ""),
+            &StructDiff::build_from(ORIGINAL, right, ""),
+            &Resolution::default()
+        );
+
+        let retimed = ORIGINAL.replace("time: 0,\n        color: 0x00FF00", "time: 4,\n        color: 0x00FF00");
+        let both = merged(&retimed, &retimed);
+        assert!(both.conflicts.is_empty());
+        assert_eq!(both.units.join("\n"), retimed);
+
+        let removed = ORIGINAL.replace("    press: {\n        lane: 0,\n        time: 0,\n        color: 0xFF0000,\n    },\n", "");
+        let recolored = removed.replace("0x00FF00", "0x00AA00");
+        let both = merged(&removed, &recolored);
+        assert!(both.conflicts.is_empty());
+        assert_eq!(both.units.join("\n"), recolored);
+
+        let inserted = ORIGINAL.replacen("objs: {", "objs: {\n    press: { lane: 3, time: 2 },", 1);
+        let both = merged(&inserted, &inserted);
+        assert!(both.conflicts.is_empty());
+        assert_eq!(both.units.join("\n"), inserted);
+    }
+
+    #[test]
+    fn merge_conflicts() {
+        let left = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00AA00"), "");
+        let right = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00BB00"), "");
+
+        let Merged { units: merged, conflicts, .. } = map().merge(&left, &right, &Resolution::default());
+        let expected = indoc!(
+            "objs: {
+                press: {
+                    lane: 0,
+                    time: 0,
+                    color: 0xFF0000,
+                },
+            <<<
+                hold: {
+                    lane: 1,
+                    time: 0,
+                    color: 0x00AA00,
+                },
+            ===
+                hold: {
+                    lane: 1,
+                    time: 0,
+                    color: 0x00BB00,
+                },
+            >>>
+            }"
+        );
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(merged.join("\n"), expected);
+    }
+
+    #[test]
+    fn merge_resolved() {
+        let left = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00AA00"), "");
+        let right = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00BB00"), "right");
+        let merged = |resolution: Resolution| map().merge(&left, &right, &resolution);
+
+        let kept_left = merged(Resolution::keep(Keep::Left));
+        assert!(kept_left.conflicts.is_empty());
+        assert_eq!(kept_left.units.join("\n"), ORIGINAL.replace("0x00FF00", "0x00AA00"));
+        assert_eq!(kept_left.resolved, vec![Resolved {
+            keys: vec![vec!["1".to_string(), "0".to_string()]],
+            kept: Keep::Left,
+            mod_id: String::new(),
+            comment: "right".to_string(),
+        }]);
+
+        let kept_both = merged(Resolution::keep(Keep::Both));
+        assert_eq!(kept_both.units.len(), ORIGINAL.lines().count() + 5);
+
+        let by_lane = |lane: &str| Resolution {
+            rules: vec![Rule { key: "lane".to_string(), matches: Regex::new(lane).unwrap(), keep: Keep::Right }],
+            otherwise: None,
+        };
+
+        assert_eq!(merged(by_lane("^1$")).units.join("\n"), ORIGINAL.replace("0x00FF00", "0x00BB00"));
+        assert_eq!(merged(by_lane("^2$")).conflicts.len(), 1);
+
+        let invalid = r#"{ "rules": [{ "key": "lane", "matches": "(", "keep": "keep-right" }] }"#;
+        assert!(Resolution::try_from(serde_json::from_str::<ResolutionDef>(invalid).unwrap()).is_err());
+    }
+
+    #[test]
+    fn merge_colliding_insertions() {
+        let note = |color: &str| format!("    press: {{\n        lane: 2,\n        time: 8,\n        color: {color},\n    }},");
+        let left = StructDiff::build_from(ORIGINAL, &ORIGINAL.replacen("objs: {", &format!("objs: {{\n{}", note("0x1")), 1), "");
+        let right = StructDiff::build_from(ORIGINAL, &ORIGINAL.replacen("    },\n}", &format!("    }},\n{}\n}}", note("0x2")), 1), "");
+
+        let Merged { units: merged, conflicts, .. } = map().merge(&left, &right, &Resolution::default());
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(&merged[1..4], ["<<<", "    press: {", "        lane: 2,"]);
+        assert_eq!(&merged[7..9], ["===", ">>>"]);
+        assert_eq!(&merged[19..22], ["<<<", "===", "    press: {"]);
+    }
+}
@@ -1,12 +1,11 @@
 use derive_more::{Deref, DerefMut};
 use itertools::Itertools;
 use rayon::prelude::*;
-//  this stupid thing has it's end private
-//  which has to do with ranges being Iter
-use std::ops::RangeInclusive;
 
+//  std::ops::RangeInclusive has it's end private
+//  which has to do with ranges being Iter
 //  which is why this is necessary
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 pub struct InclRange {
     pub lower: usize,
     pub upper: usize,
@@ -17,10 +16,6 @@ impl InclRange {
         Self { lower, upper }
     }
 
-    pub fn iter(self) -> RangeInclusive<usize> {
-        self.lower..=self.upper
-    }
-
     pub fn contains(&self, &value: &usize) -> bool {
         self.lower <= value && value <= self.upper
     }
@@ -63,6 +58,18 @@ impl RangeVec {
         self.0
     }
 
+    //  Byte offsets of every line, each including its line break
+    pub fn line_spans(text: &str) -> Self {
+        text.split_inclusive('\n')
+            .scan(0, |offset, line| {
+                let span = InclRange::new(*offset, *offset + line.len() - 1);
+                *offset += line.len();
+                Some(span)
+            })
+            .collect::<Vec<_>>()
+            .into()
+    }
+
     pub fn pre_ops(mut self) -> Self {
         self.dedup();
         self.sort_by_key(|r| r.lower);
@@ -121,6 +128,34 @@ impl RangeVec {
             .into()
     }
 
+    pub fn difference_with(self, other: Self) -> Self {
+        let other = other.pre_ops().flattened();
+        let cut = |range: &InclRange| {
+            let mut lower = range.lower;
+            let mut pieces = vec![];
+
+            for cut in other.iter().filter(|cut| intersection(range, cut).is_some()) {
+                if lower < cut.lower {
+                    pieces.push(InclRange::new(lower, cut.lower - 1));
+                }
+                lower = lower.max(cut.upper + 1);
+            }
+
+            if lower <= range.upper {
+                pieces.push(InclRange::new(lower, range.upper));
+            }
+
+            pieces
+        };
+
+        self.pre_ops()
+            .flattened()
+            .iter()
+            .flat_map(cut)
+            .collect::<Vec<_>>()
+            .into()
+    }
+
     pub fn inverse(self, exclusive_lim: usize) -> Self {
         let mut offsets = self
             .pre_ops()
@@ -243,4 +278,16 @@ mod tests {
         let expected = RangeVec::from([(0, 10), (12, 20)]);
         assert_eq!(rv.that_overlap(other), expected);
     }
+
+    #[test]
+    fn range_vec_difference() {
+        let rv = RangeVec::from([(0, 10), (12, 20)]);
+        let other = RangeVec::from([(2, 4), (7, 12), (20, 25)]);
+        let expected = RangeVec::from([(0, 1), (5, 6), (13, 19)]);
+        assert_eq!(rv.difference_with(other), expected);
+
+        let rv = RangeVec::from([(3, 5)]);
+        let other = RangeVec::from([(0, 10)]);
+        assert_eq!(rv.difference_with(other), RangeVec::from(Vec::<InclRange>::new()));
+    }
 }
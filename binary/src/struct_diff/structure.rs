@@ -1,32 +1,307 @@
 use regex::Regex;
-use super::{divider::*, diff::*, range_vec::*};
-use rayon::prelude::*;
+use super::{divider::*, diff::*, merge::*, range_vec::*};
+use itertools::Itertools;
+use diff::Result as DiffResult;
 use crate::utils::*;
-use std::ops::RangeInclusive;
+use std::{collections::HashMap, ops::Range, sync::LazyLock};
+use thiserror::Error;
 
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:^|[^0-9.])(-?[0-9]+(?:\.[0-9]+)?)").unwrap());
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[A-Za-z_][A-Za-z0-9_-]*").unwrap());
+
+//  The first word of a line or pattern, which is what things are named by when nothing else names them
+fn first_word(text: &str) -> Option<String> {
+    WORD.find(text).map(|word| word.as_str().to_string())
+}
+
+//  E.g. "hold object at lines 11-15", with lines counted from 1
+fn describe(range: &InclRange, kind: Option<&str>) -> String {
+    let kind = kind.map_or("object".to_string(), |kind| format!("{kind} object"));
+    match range.lower == range.upper {
+        true => format!("{kind} at line {}", range.lower + 1),
+        false => format!("{kind} at lines {}-{}", range.lower + 1, range.upper + 1),
+    }
+}
+
+//  How two values of a key are decided to be the same. Overlap treats the smallest and
+//  largest numbers in a value as the ends of a range, e.g. a hold from 1000-1500.
+//  Values that aren't numbers are compared exactly by the numeric comparators.
+//  Region compares the boxes spatial keys produce, see Key::Spatial.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Comparator {
+    #[default]
+    Exact,
+    CaseInsensitive,
+    Tolerance(f64),
+    Overlap,
+    Region,
+}
+
+impl Comparator {
+    fn numbers(value: &str) -> Vec<f64> {
+        NUMBER
+            .captures_iter(value)
+            .filter_map(|captures| captures[1].parse().ok())
+            .collect()
+    }
+
+    pub fn same(&self, a: &str, b: &str) -> bool {
+        let bounds = |numbers: Vec<f64>| numbers
+            .iter()
+            .cloned()
+            .reduce(f64::min)
+            .zip(numbers.iter().cloned().reduce(f64::max));
+
+        match self {
+            Self::Exact => a == b,
+            Self::CaseInsensitive => a.to_lowercase() == b.to_lowercase(),
+            Self::Tolerance(tolerance) => match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+                (Ok(a), Ok(b)) => (a - b).abs() <= *tolerance,
+                _ => a == b,
+            },
+            Self::Overlap => match (bounds(Self::numbers(a)), bounds(Self::numbers(b))) {
+                (Some((a_lower, a_upper)), Some((b_lower, b_upper))) => a_lower <= b_upper && b_lower <= a_upper,
+                _ => a == b,
+            },
+            //  Boxes only touching along an edge don't overlap, unless one of them is flat
+            //  along that axis, so a point on the edge of a box still counts
+            Self::Region => match (Self::numbers(a).as_slice(), Self::numbers(b).as_slice()) {
+                (&[a_left, a_top, a_right, a_bottom], &[b_left, b_top, b_right, b_bottom]) => {
+                    let axis = |a_lower: f64, a_upper: f64, b_lower: f64, b_upper: f64| {
+                        let (lower, upper) = (a_lower.max(b_lower), a_upper.min(b_upper));
+                        lower < upper || (lower == upper && (a_lower == a_upper || b_lower == b_upper))
+                    };
+
+                    axis(a_left, a_right, b_left, b_right) && axis(a_top, a_bottom, b_top, b_bottom)
+                }
+                _ => a == b,
+            },
+        }
+    }
+}
+
+//  How far a positioned object reaches from its x and y
+#[derive(Debug, Clone)]
+pub enum Extent {
+    Point,
+    Size { w: Box<Key>, h: Box<Key> },
+    Radius(Box<Key>),
+}
+
+//  Either a pair of patterns, the first finding the key in a line and the second
+//  picking the value out of it, or a column of a table looked up by its header.
+//  Spatial keys combine other keys into the box an object covers, written as
+//  "left top right bottom", and always compare by Region.
 #[derive(Debug, Clone)]
-pub struct Key {
-    pub fuzzed: Option<Regex>,
-    pub strict: Regex,
+pub enum Key {
+    Pattern {
+        fuzzed: Option<Regex>,
+        strict: Regex,
+        compare: Comparator,
+    },
+    Column {
+        name: String,
+        compare: Comparator,
+    },
+    Spatial {
+        x: Box<Key>,
+        y: Box<Key>,
+        extent: Extent,
+    },
 }
 
 impl Key {
-    fn find<'a, S>(&'a self, line: S) -> Vec<String>
+    pub fn compare(&self) -> Comparator {
+        match self {
+            Self::Pattern { compare, .. } | Self::Column { compare, .. } => *compare,
+            Self::Spatial { .. } => Comparator::Region,
+        }
+    }
+
+    //  How the key is referred to in errors
+    pub fn name(&self) -> String {
+        match self {
+            Self::Pattern { fuzzed, strict, .. } => fuzzed
+                .as_ref()
+                .map_or(strict.as_str(), |fuzzed| fuzzed.as_str())
+                .to_string(),
+            Self::Column { name, .. } => name.clone(),
+            Self::Spatial { x, y, .. } => format!("region at {}, {}", x.name(), y.name()),
+        }
+    }
+
+    //  How the key is referred to in paths, e.g. lane for "lane: [0-9]*"
+    pub fn label(&self) -> String {
+        match self {
+            Self::Pattern { .. } => first_word(&self.name()).unwrap_or_else(|| self.name()),
+            Self::Column { name, .. } => name.clone(),
+            Self::Spatial { .. } => "region".to_string(),
+        }
+    }
+
+    fn find<S>(&self, line: S, header: Option<&Header>) -> Vec<String>
     where
-        S: AsRef<str> + 'a
+        S: AsRef<str>
     {
-        let strict_find = |fuzzed: regex::Matches<'a, 'a>| fuzzed
-            .flat_map(|token| self
-                .strict
-                .find_iter(token.as_str())
-                .map(|found| found.as_str())
-                .flat_map(|found| (!found.is_empty()).then(|| found.to_string()))
+        match self {
+            Self::Pattern { fuzzed, strict, .. } => {
+                let strict_find = |token: regex::Match| strict
+                    .find_iter(token.as_str())
+                    .map(|found| found.as_str())
+                    .flat_map(|found| (!found.is_empty()).then(|| found.to_string()))
+                    .collect::<Vec<_>>();
+
+                fuzzed
+                    .as_ref()
+                    .map_or(vec![], |regex| regex
+                        .find_iter(line.as_ref())
+                        .flat_map(strict_find)
+                        .collect()
+                    )
+            }
+            Self::Column { name, .. } => header
+                .and_then(|header| header.cell(line.as_ref(), name))
+                .into_iter()
+                .collect(),
+            Self::Spatial { .. } => vec![],
+        }
+    }
+
+    //  The one value of this key in an object
+    fn value<'a>(&self, lines: &[String], range: InclRange, header: &dyn Fn(usize) -> Option<&'a Header>) -> Result<String, KeyError> {
+        let number = |key: &Key| key
+            .value(lines, range, header)
+            .and_then(|value| value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| KeyError::NotNumeric { key: key.name(), line: range.lower, value })
             );
 
-        self.fuzzed
-            .as_ref()
-            .map(|regex| regex.find_iter(line.as_ref()))
-            .map_or(vec![], |matches| strict_find(matches).collect())
+        if let Self::Spatial { x, y, extent } = self {
+            let (x, y) = (number(x)?, number(y)?);
+            let (left, top, right, bottom) = match extent {
+                Extent::Point => (x, y, x, y),
+                Extent::Size { w, h } => (x, y, x + number(w)?, y + number(h)?),
+                Extent::Radius(radius) => {
+                    let radius = number(radius)?;
+                    (x - radius, y - radius, x + radius, y + radius)
+                }
+            };
+
+            return Ok(format!("{left} {top} {right} {bottom}"));
+        }
+
+        let values = (range.lower..=range.upper)
+            .flat_map(|i| self.find(&lines[i], header(i)))
+            .unique()
+            .collect::<Vec<_>>();
+
+        match values.as_slice() {
+            [] => Err(KeyError::Missing { key: self.name(), line: range.lower }),
+            [value] => Ok(value.clone()),
+            _ => Err(KeyError::Ambiguous { key: self.name(), line: range.lower, values }),
+        }
+    }
+}
+
+//  Objects that live inside the objects of whichever divider this is nested under
+#[derive(Debug, Clone)]
+pub struct Nested {
+    pub name: String,
+    pub divider: Divider,
+    pub nested: Vec<Nested>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjNode {
+    pub kind: Option<String>,
+    pub range: InclRange,
+    pub children: Vec<ObjNode>,
+}
+
+impl ObjNode {
+    fn leaves(&self) -> Vec<&ObjNode> {
+        if self.children.is_empty() {
+            vec![self]
+        } else {
+            self.children
+                .iter()
+                .flat_map(|child| child.leaves())
+                .collect()
+        }
+    }
+
+    //  Names of the objects down to each leaf, see Config::trails
+    fn trails(&self, name: &dyn Fn(&ObjNode) -> String, trail: &[String]) -> Vec<(InclRange, Vec<String>)> {
+        let trail = trail
+            .iter()
+            .cloned()
+            .chain([name(self)])
+            .collect::<Vec<_>>();
+
+        match self.children.is_empty() {
+            true => vec![(self.range, trail)],
+            false => self.children
+                .iter()
+                .flat_map(|child| child.trails(name, &trail))
+                .collect(),
+        }
+    }
+
+    //  Lines of this object that aren't part of any of its children
+    fn own(&self) -> RangeVec {
+        RangeVec::from([self.range]).difference_with(
+            self.children
+                .iter()
+                .map(|child| child.range)
+                .collect::<Vec<_>>()
+                .into()
+        )
+    }
+
+    //  Changed leaves are kept whole, their ancestors only keep their own lines
+    fn context(&self, indices: &RangeVec) -> Vec<InclRange> {
+        if !indices.iter().any(|index| self.range.contains(&index.lower)) {
+            return vec![];
+        }
+
+        if self.children.is_empty() {
+            return vec![self.range];
+        }
+
+        self.own()
+            .dewrap()
+            .into_iter()
+            .chain(self.children.iter().flat_map(|child| child.context(indices)))
+            .collect()
+    }
+}
+
+//  Each nested kind divided once up front, in the same shape as the nesting
+struct Division<'a> {
+    kind: &'a Nested,
+    ranges: Vec<InclRange>,
+    kinds: HashMap<InclRange, String>,
+    nested: Vec<Division<'a>>,
+}
+
+impl<'a> Division<'a> {
+    fn of<F>(nested: &'a [Nested], divide: &F) -> Vec<Self>
+    where
+        F: Fn(&Divider) -> Divided
+    {
+        nested
+            .iter()
+            .map(|kind| {
+                let divided = divide(&kind.divider);
+                Division {
+                    kind,
+                    ranges: divided.ranges.pre_ops().dewrap(),
+                    kinds: divided.kinds.into_iter().collect(),
+                    nested: Self::of(&kind.nested, divide)
+                }
+            })
+            .collect()
     }
 }
 
@@ -34,7 +309,9 @@ impl Key {
 pub struct Config {
     pub filter: Option<Divider>,
     pub expander: Option<Divider>,
+    pub nested: Vec<Nested>,
     pub keys: Vec<Key>,
+    pub markers: Markers,
 }
 
 //  Can't alias the constraints because I would need GATs
@@ -47,20 +324,328 @@ impl Config {
     {
         self.filter
             .as_ref()
-            .map(|filter| filter.divide(iter.clone()))
+            .map(|filter| filter.divide(iter.clone()).ranges)
             .unwrap_or_else(|| [(0, iter.clone().count())].into())
     }
 
-    pub fn objs<I, S>(&self, iter: I) -> RangeVec 
+    fn children(divisions: &[Division], parent: InclRange) -> Vec<ObjNode> {
+        let mut children = divisions
+            .iter()
+            .flat_map(|division| {
+                let from = division.ranges.partition_point(|range| range.lower < parent.lower);
+                let mut candidates = division.ranges[from..]
+                    .iter()
+                    .take_while(|range| range.lower <= parent.upper)
+                    .filter(|range| range.upper <= parent.upper && **range != parent)
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                candidates.sort_by_key(|range| (range.lower, std::cmp::Reverse(range.upper)));
+
+                //  Only the outermost matches of a kind are its children, the rest are theirs
+                let mut reach = None;
+                candidates
+                    .into_iter()
+                    .filter(move |range| {
+                        let outermost = reach.is_none_or(|reach| reach < range.upper);
+                        if outermost {
+                            reach = Some(range.upper);
+                        }
+                        outermost
+                    })
+                    .map(|range| ObjNode {
+                        kind: Some(division.kinds.get(&range).unwrap_or(&division.kind.name).clone()),
+                        range,
+                        children: Self::children(&division.nested, range)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        children.sort_by_key(|child| child.range.lower);
+        children
+    }
+
+    //  Objects take the kind their divider captured, falling back to the name of their nested kind
+    fn grow<F>(&self, roots: RangeVec, kinds: Vec<(InclRange, String)>, divide: F) -> Vec<ObjNode>
+    where
+        F: Fn(&Divider) -> Divided
+    {
+        let divisions = Division::of(&self.nested, &divide);
+        let kinds = kinds.into_iter().collect::<HashMap<_, _>>();
+
+        roots
+            .dewrap()
+            .into_iter()
+            .map(|range| ObjNode {
+                kind: kinds.get(&range).cloned(),
+                range,
+                children: Self::children(&divisions, range)
+            })
+            .collect()
+    }
+
+    pub fn tree<I, S>(&self, iter: I) -> Vec<ObjNode>
     where
         I: Iterator<Item = S> + Clone + Send + Sync,
         S: AsRef<str> + Send
     {
-        self.expander
+        let expanded = self.expander
             .as_ref()
-            .map(|expander| expander.divide(iter.clone()))
+            .map(|expander| expander.divide(iter.clone()));
+
+        let kinds = expanded.as_ref().map_or(vec![], |expanded| expanded.kinds.clone());
+        let roots = expanded
+            .map(|expanded| expanded.ranges)
             .unwrap_or_else(|| (0..iter.clone().count()).into())
-            .intersection_with(self.filtered(iter))
+            .intersection_with(self.filtered(iter.clone()));
+
+        self.grow(roots, kinds, |divider| divider.divide(iter.clone()))
+    }
+
+    //  Same as tree but with byte offsets into the text rather than line indices
+    pub fn span_tree(&self, text: &str) -> Vec<ObjNode> {
+        let filtered = self.filter
+            .as_ref()
+            .map(|filter| filter.divide_spans(text).ranges)
+            .unwrap_or_else(|| [(0, text.len().saturating_sub(1))].into());
+
+        let expanded = self.expander
+            .as_ref()
+            .map(|expander| expander.divide_spans(text));
+
+        let kinds = expanded.as_ref().map_or(vec![], |expanded| expanded.kinds.clone());
+        let roots = expanded
+            .map(|expanded| expanded.ranges)
+            .unwrap_or_else(|| RangeVec::line_spans(text))
+            .intersection_with(filtered);
+
+        self.grow(roots, kinds, |divider| divider.divide_spans(text))
+    }
+
+    //  Offsets to cut the text at so every object, and every part of a parent
+    //  outside its children, ends up in a piece of its own
+    pub fn cuts(&self, text: &str) -> Vec<usize> {
+        fn bounds(node: &ObjNode) -> Vec<usize> {
+            [node.range.lower, node.range.upper + 1]
+                .into_iter()
+                .chain(node.children.iter().flat_map(bounds))
+                .collect()
+        }
+
+        let filtered = self.filter
+            .as_ref()
+            .map_or(vec![], |filter| filter.divide_spans(text).ranges.dewrap());
+
+        let mut cuts = self
+            .span_tree(text)
+            .iter()
+            .flat_map(bounds)
+            .chain(filtered.into_iter().flat_map(|range| [range.lower, range.upper + 1]))
+            .filter(|&cut| 0 < cut && cut < text.len())
+            .collect::<Vec<_>>();
+
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts
+    }
+
+    //  The innermost objects, which is where keys are looked for, along with their kinds
+    pub fn objs<I, S>(&self, iter: I) -> Vec<(InclRange, Option<String>)>
+    where
+        I: Iterator<Item = S> + Clone + Send + Sync,
+        S: AsRef<str> + Send
+    {
+        self.tree(iter)
+            .iter()
+            .flat_map(|node| node.leaves())
+            .map(|leaf| (leaf.range, leaf.kind.clone()))
+            .collect()
+    }
+
+    //  Where each innermost object sits, as the names of the filtered region and objects it's
+    //  inside followed by its own. Objects are named by their kind, and anything without one
+    //  by the first word of the line it starts at, e.g. objs for "objs: {".
+    pub fn trails<I, S>(&self, iter: I) -> Vec<(InclRange, Vec<String>)>
+    where
+        I: Iterator<Item = S> + Clone + Send + Sync,
+        S: AsRef<str> + Send
+    {
+        let lines = iter.clone().collect::<Vec<_>>();
+        let word = |i: usize| lines
+            .get(i)
+            .and_then(|line| first_word(line.as_ref()))
+            .unwrap_or_else(|| "object".to_string());
+
+        let regions = self.filter
+            .as_ref()
+            .map_or(vec![], |filter| filter.divide(iter.clone()).ranges.dewrap());
+
+        let name = |node: &ObjNode| node.kind
+            .clone()
+            .unwrap_or_else(|| word(node.range.lower));
+
+        self.tree(iter)
+            .iter()
+            .flat_map(|node| {
+                let region = regions
+                    .iter()
+                    .find(|region| region.contains(&node.range.lower))
+                    .map(|region| word(region.lower));
+
+                node.trails(&name, &region.into_iter().collect::<Vec<_>>())
+            })
+            .collect()
+    }
+
+    //  Objects touching any of the indices along with their parents' own lines
+    pub fn context<I, S>(&self, iter: I, indices: &RangeVec) -> RangeVec
+    where
+        I: Iterator<Item = S> + Clone + Send + Sync,
+        S: AsRef<str> + Send
+    {
+        self.tree(iter)
+            .iter()
+            .flat_map(|node| node.context(indices))
+            .collect::<Vec<_>>()
+            .into()
+    }
+
+    //  Whether two objects' keys identify the same spot, key by key with each key's comparator.
+    //  Keys supplied by dividers come after the configured ones and are compared exactly.
+    pub fn same(&self, a: &[String], b: &[String]) -> bool {
+        a.len() == b.len() && a
+            .iter()
+            .zip(b.iter())
+            .enumerate()
+            .all(|(i, (a, b))| self.keys
+                .get(i)
+                .map_or(Comparator::Exact, Key::compare)
+                .same(a, b)
+            )
+    }
+
+    //  Every divider in the config, named the way diagnostics report them
+    fn dividers(&self) -> Vec<(String, &Divider)> {
+        fn nested_dividers(nested: &[Nested]) -> Vec<(String, &Divider)> {
+            nested
+                .iter()
+                .flat_map(|kind| [(kind.name.clone(), &kind.divider)]
+                    .into_iter()
+                    .chain(nested_dividers(&kind.nested))
+                )
+                .collect()
+        }
+
+        [("filter", &self.filter), ("expander", &self.expander)]
+            .into_iter()
+            .filter_map(|(name, divider)| divider.as_ref().map(|divider| (name.to_string(), divider)))
+            .chain(nested_dividers(&self.nested))
+            .collect()
+    }
+
+    pub fn headers<I, S>(&self, iter: I) -> Vec<Header>
+    where
+        I: Iterator<Item = S> + Clone,
+        S: AsRef<str>
+    {
+        self.dividers()
+            .into_iter()
+            .flat_map(|(_, divider)| divider.headers(iter.clone()))
+            .collect()
+    }
+
+    //  Keys dividers found for their objects themselves
+    pub fn supplied_keys<I, S>(&self, iter: I) -> Vec<(InclRange, Vec<String>)>
+    where
+        I: Iterator<Item = S> + Clone,
+        S: AsRef<str>
+    {
+        self.dividers()
+            .into_iter()
+            .filter(|(_, divider)| matches!(divider, Divider::External { .. }))
+            .flat_map(|(_, divider)| divider.divide(iter.clone()).keys)
+            .collect()
+    }
+
+    pub fn diagnostics<I, S>(&self, iter: I) -> Vec<(String, Diagnostic)>
+    where
+        I: Iterator<Item = S> + Clone,
+        S: AsRef<str>
+    {
+        self.dividers()
+            .into_iter()
+            .flat_map(|(name, divider)| divider
+                .divide(iter.clone())
+                .diagnostics
+                .into_iter()
+                .map(move |diagnostic| (name.clone(), diagnostic))
+            )
+            .collect()
+    }
+}
+
+//  Objects are identified by one value per key, anything else is an error in the map or the config.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KeyError {
+    #[error("object at line {} has no value for key {key}", .line + 1)]
+    Missing { key: String, line: usize },
+    #[error("object at line {} has several values for key {key}: {}", .line + 1, .values.join(", "))]
+    Ambiguous { key: String, line: usize, values: Vec<String> },
+    #[error("object at line {} has {value} for key {key}, which isn't a number", .line + 1)]
+    NotNumeric { key: String, line: usize, value: String },
+}
+
+//  Edits to a minimal view that can't be carried over to the map it was inflated from
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ViewError {
+    #[error("line {} of the view stands in for lines that weren't shown, so it can't be edited", .line + 1)]
+    Elided { line: usize },
+}
+
+//  An innermost object along with the values of every key, in the order the config lists them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Obj {
+    pub range: InclRange,
+    pub kind: Option<String>,
+    pub key: Vec<String>,
+}
+
+//  Objects that could be identified, and why the others couldn't
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjIndex {
+    pub objs: Vec<Obj>,
+    pub errors: Vec<KeyError>,
+}
+
+//  A changed object, by its path (see Structure::paths) and the lines it covers in the original.
+//  Lines are empty for objects a mod adds, sitting where they're inserted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breadcrumb {
+    pub path: String,
+    pub lines: Range<usize>,
+}
+
+//  How much of a map is shown around changes. Inclusive views show changed objects whole,
+//  exclusive ones only their changed units. Context is how many neighbouring objects either
+//  side of each changed one are shown too, and outside whether everything outside the filter
+//  is. Skipped regions are replaced by the elision marker if there is one, e.g. "...".
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inflation {
+    pub exclusive: bool,
+    pub context: usize,
+    pub outside: bool,
+    pub elision: Option<String>,
+}
+
+impl Default for Inflation {
+    fn default() -> Self {
+        Self {
+            exclusive: false,
+            context: 0,
+            outside: true,
+            elision: None,
+        }
     }
 }
 
@@ -70,12 +655,48 @@ pub struct Structure {
     pub config: Config,
 }
 
+//  Where a line of an inflated view came from, by its index in the map and the map
+//  once patched. Removed lines are only in the map, added ones only in the patched
+//  map, and inserted ones are elision markers in neither.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provenance {
+    Kept { original: usize, patched: usize },
+    Removed(usize),
+    Added(usize),
+    Inserted,
+}
+
+//  A minimal view along with where each of its lines came from, and the objects it shows
+//  lines of as the lines of the view they're on, with their kinds. The kinds are those of
+//  the map the lines were taken from, since a view cut down to changed lines or elided
+//  can't always be divided into the same objects again.
+#[derive(Debug, Clone)]
+pub struct Inflated {
+    pub structure: Structure,
+    pub lines: Vec<Provenance>,
+    pub objs: Vec<(InclRange, Option<String>)>,
+}
+
+impl Inflated {
+    //  Same as Structure::describe for every object in the view
+    pub fn describe(&self) -> Vec<String> {
+        self.objs
+            .iter()
+            .map(|(range, kind)| describe(range, kind.as_deref()))
+            .collect()
+    }
+}
+
+//  Inflated views of each side around the conflicts, and the conflicts themselves
+#[derive(Debug, Clone)]
+pub struct Conflicts {
+    pub left: Inflated,
+    pub right: Inflated,
+    pub objects: Vec<Conflict>,
+}
 
-//  Todo:
-//  
-//      Add errors for multiple key matches/no key matches
-//
 impl Structure {
+    #[cfg(test)]
     fn new(text: String, config: Config) -> Structure {
         Structure {
             contents: text
@@ -86,104 +707,438 @@ impl Structure {
         }
     }
 
-    fn keys(&self) -> Vec<(usize, Vec<String>)> {
-        let to_keys = |span: RangeInclusive<usize>| span
-            .clone()
-            .zip(self.contents[span].iter())
-            .map(|(i, line)| (i, self
-                    .config
-                    .keys
-                    .iter()
-                    .flat_map(|key| key.find(line.clone()).into_iter())
-                    .collect::<Vec<_>>()
-                )
-            );
+    pub fn diagnostics(&self) -> Vec<(String, Diagnostic)> {
+        self.config.diagnostics(self.contents.iter())
+    }
+
+    pub fn patched(&self, modifications: &StructDiff) -> Structure {
+        Self {
+            contents: modifications.patch_units(&self.contents),
+            config: self.config.clone()
+        }
+    }
 
+    //  Objects touching any of the indices, described for people rather than by their lines
+    pub fn describe(&self, indices: &[usize]) -> Vec<String> {
         self.config
             .objs(self.contents.iter())
-            .par_iter()
-            .map(|range| range.lower..=range.upper)
-            .flat_map(|range| to_keys(range).collect::<Vec<_>>().into_par_iter())
-            .collect::<Vec<_>>()
+            .into_iter()
+            .filter(|(range, _)| indices.iter().any(|i| range.contains(i)))
+            .map(|(range, kind)| describe(&range, kind.as_deref()))
+            .collect()
+    }
+
+    //  Innermost objects by a path that stays the same wherever they move, e.g.
+    //  "objs > hold[lane=1,time=0]". Keys supplied by dividers are listed without a label.
+    pub fn paths(&self) -> Vec<(InclRange, String)> {
+        let objs = self.index().objs;
+        let labels = self.config.keys
+            .iter()
+            .map(|key| Some(key.label()))
+            .chain(std::iter::repeat(None));
+
+        self.config
+            .trails(self.contents.iter())
+            .into_iter()
+            .map(|(range, trail)| {
+                let key = objs
+                    .iter()
+                    .find(|obj| obj.range == range)
+                    .map(|obj| obj.key
+                        .iter()
+                        .zip(labels.clone())
+                        .map(|(value, label)| label.map_or(value.clone(), |label| format!("{label}={value}")))
+                        .join(",")
+                    )
+                    .map_or(String::new(), |key| format!("[{key}]"));
+
+                (range, format!("{}{key}", trail.join(" > ")))
+            })
+            .collect()
+    }
+
+    //  Every object a mod changes, with where it is in this structure. Objects the mod
+    //  only adds are placed between whatever lines it kept either side of them.
+    pub fn breadcrumbs(&self, modifications: &StructDiff) -> Vec<Breadcrumb> {
+        let patched = self.patched(modifications);
+        let original_kept = Self::kept(self.contents.len(), &modifications.removed);
+        let patched_kept = Self::kept(patched.contents.len(), &modifications.added);
+
+        let touching = |range: &InclRange, indices: &[usize]| indices
+            .iter()
+            .any(|i| range.contains(i));
+
+        let mut breadcrumbs = self
+            .paths()
+            .into_iter()
+            .filter(|(range, _)| touching(range, &modifications.removed))
+            .map(|(range, path)| Breadcrumb { path, lines: range.lower..range.upper + 1 })
+            .collect::<Vec<_>>();
+
+        for (range, path) in patched.paths().into_iter().filter(|(range, _)| touching(range, &modifications.added)) {
+            if breadcrumbs.iter().any(|breadcrumb| breadcrumb.path == path) {
+                continue;
+            }
+
+            let before = patched_kept.partition_point(|&i| i < range.lower);
+            let after = patched_kept.partition_point(|&i| i <= range.upper);
+            let from = before.checked_sub(1).map_or(0, |before| original_kept[before] + 1);
+            let to = original_kept.get(after).copied().unwrap_or(self.contents.len());
+
+            breadcrumbs.push(Breadcrumb { path, lines: from..to.max(from) });
+        }
+
+        breadcrumbs.sort_by_key(|breadcrumb| breadcrumb.lines.start);
+        breadcrumbs
+    }
+
+    //  Every object a mod removes from this structure or adds to it once patched
+    pub fn summary(&self, modifications: &StructDiff) -> Vec<String> {
+        let removed = self
+            .describe(&modifications.removed)
+            .into_iter()
+            .map(|object| format!("removes {object}"));
+
+        let added = self
+            .patched(modifications)
+            .describe(&modifications.added)
+            .into_iter()
+            .map(|object| format!("adds {object}"));
+
+        removed.chain(added).collect()
+    }
+
+    //  Objects without any keys can't be identified, so they're left out without an error
+    pub fn index(&self) -> ObjIndex {
+        let headers = self.config.headers(self.contents.iter());
+        let header = |i: usize| headers
+            .iter()
+            .find(|header| header.region.contains(&i));
+
+        let supplied = self.config.supplied_keys(self.contents.iter());
+        let supplied = |range: InclRange| supplied
+            .iter()
+            .filter(move |(supplied, _)| supplied.lower == range.lower)
+            .flat_map(|(_, keys)| keys.iter().cloned());
+
+        let identify = |(range, kind): (InclRange, Option<String>)| {
+            let mut key = vec![];
+            let mut errors = vec![];
+
+            for config_key in self.config.keys.iter() {
+                match config_key.value(&self.contents, range, &header) {
+                    Ok(value) => key.push(value),
+                    Err(error) => errors.push(error),
+                }
+            }
+
+            key.extend(supplied(range));
+            match errors.is_empty() {
+                true => (!key.is_empty()).then_some(Ok(Obj { range, kind, key })),
+                false => Some(Err(errors)),
+            }
+        };
+
+        let mut index = ObjIndex::default();
+        for identified in self.config.objs(self.contents.iter()).into_iter().filter_map(identify) {
+            match identified {
+                Ok(obj) => index.objs.push(obj),
+                Err(errors) => index.errors.extend(errors),
+            }
+        }
+        index
     }
 
-    fn inflate(config: &Config, patched: &[String], indices: &[usize]) -> Structure {
-        let mut indices: RangeVec = indices.to_vec().into();
+    //  Units of a minimal view of the changes at the indices, see Inflation
+    fn shown(config: &Config, patched: &[String], indices: &[usize], inflation: &Inflation) -> RangeVec {
+        //  A diff's indices can count a trailing empty line that isn't among the units
+        let mut indices: RangeVec = indices
+            .iter()
+            .copied()
+            .filter(|&i| i < patched.len())
+            .collect::<Vec<_>>()
+            .into();
 
         indices.dedup();
 
-        let objs = config
+        let mut leaves = config
             .objs(patched.iter())
-            .that_overlap(indices);
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect::<Vec<_>>();
+
+        leaves.sort_by_key(|range| range.lower);
 
-        let inflated = config
-            .filtered(patched.iter())
-            .inverse(patched.len())
-            .union_with(objs)
-            .pre_ops()
+        let overlapping = RangeVec::from(leaves.clone()).that_overlap(indices.clone());
+        let changed = leaves
             .iter()
-            .flat_map(|r| &patched[r.lower..=r.upper])
-            .cloned()
+            .positions(|leaf| overlapping.contains(leaf))
             .collect::<Vec<_>>();
 
-        Self {
+        let neighbours = changed
+            .iter()
+            .flat_map(|&i| i.saturating_sub(inflation.context)..=(i + inflation.context).min(leaves.len().saturating_sub(1)))
+            .unique()
+            .filter(|i| changed.binary_search(i).is_err())
+            .map(|i| InclRange::from(leaves[i].lower))
+            .collect::<Vec<_>>();
+
+        let objs = config.context(patched.iter(), &indices.clone().union_with(neighbours.into()));
+
+        //  Exclusive views only show the changed units of changed objects
+        let objs = match inflation.exclusive {
+            true => objs
+                .difference_with(changed.iter().map(|&i| leaves[i]).collect::<Vec<_>>().into())
+                .union_with(indices),
+            false => objs,
+        };
+
+        //  Shown rows of a table need its start and header lines for their columns to make sense
+        let headers = config
+            .headers(patched.iter())
+            .into_iter()
+            .filter(|header| objs.iter().any(|obj| header.region.contains(&obj.lower)))
+            .map(|header| InclRange::new(header.region.lower, header.region.lower + 1))
+            .collect::<Vec<_>>();
+
+        let objs = objs.union_with(headers.into());
+
+        match inflation.outside {
+            true => config
+                .filtered(patched.iter())
+                .inverse(patched.len())
+                .union_with(objs),
+            false => objs,
+        }
+    }
+
+    //  Along with the index each unit was taken from, none for elision markers
+    fn inflate(config: &Config, patched: &[String], indices: &[usize], inflation: &Inflation) -> (Structure, Vec<Option<usize>>) {
+        let mut inflated = vec![];
+        let mut taken = vec![];
+        let mut next = 0;
+
+        let elide = |inflated: &mut Vec<String>, taken: &mut Vec<Option<usize>>| {
+            if let Some(marker) = &inflation.elision {
+                inflated.push(marker.clone());
+                taken.push(None);
+            }
+        };
+
+        for range in Self::shown(config, patched, indices, inflation).pre_ops().iter() {
+            if next < range.lower {
+                elide(&mut inflated, &mut taken);
+            }
+
+            let from = next.max(range.lower);
+            inflated.extend(patched[from..=range.upper].iter().cloned());
+            taken.extend((from..=range.upper).map(Some));
+            next = next.max(range.upper + 1);
+        }
+
+        if next < patched.len() {
+            elide(&mut inflated, &mut taken);
+        }
+
+        let structure = Self {
             contents: inflated,
             config: config.clone()
+        };
+
+        (structure, taken)
+    }
+
+    //  Innermost objects of the units a view was taken from that it shows any of, as the
+    //  lines of the view they're on
+    fn shown_objs(config: &Config, source: &[String], taken: &[Option<usize>]) -> Vec<(InclRange, Option<String>)> {
+        let objs = config.objs(source.iter());
+        let mut shown: Vec<(InclRange, Option<String>)> = vec![];
+        let mut last = None;
+
+        for (line, unit) in taken.iter().enumerate() {
+            let Some(unit) = *unit else { continue };
+            let i = objs.partition_point(|(range, _)| range.upper < unit);
+            let Some((range, kind)) = objs.get(i) else { continue };
+
+            match shown.last_mut() {
+                Some((lines, _)) if last == Some(i) => lines.upper = line,
+                _ if range.contains(&unit) => {
+                    shown.push((InclRange::new(line, line), kind.clone()));
+                    last = Some(i);
+                }
+                _ => {}
+            }
         }
+
+        shown
+    }
+
+    //  Where units taken from this structure, or from it once patched, are in the other one
+    fn provenance(&self, modifications: &StructDiff, taken: Vec<Option<usize>>, from_patched: bool) -> Vec<Provenance> {
+        let patched_len = self.contents.len() + modifications.added.len() - modifications.removed.len();
+        let original_kept = Self::kept(self.contents.len(), &modifications.removed);
+        let patched_kept = Self::kept(patched_len, &modifications.added);
+
+        let (from, to, changed) = match from_patched {
+            true => (&patched_kept, &original_kept, &modifications.added),
+            false => (&original_kept, &patched_kept, &modifications.removed),
+        };
+
+        taken
+            .into_iter()
+            .map(|unit| match unit {
+                None => Provenance::Inserted,
+                Some(i) if changed.binary_search(&i).is_ok() => match from_patched {
+                    true => Provenance::Added(i),
+                    false => Provenance::Removed(i),
+                },
+                Some(i) => {
+                    let other = to[from.partition_point(|&kept| kept < i)];
+                    match from_patched {
+                        true => Provenance::Kept { original: other, patched: i },
+                        false => Provenance::Kept { original: i, patched: other },
+                    }
+                }
+            })
+            .collect()
+    }
+
+    //  View of this structure once patched around the given patched units
+    fn inflate_patched(&self, modifications: &StructDiff, indices: &[usize], inflation: &Inflation) -> Inflated {
+        let remade = modifications.patch_units(&self.contents);
+        let (structure, taken) = Self::inflate(&self.config, &remade, indices, inflation);
+        let objs = Self::shown_objs(&self.config, &remade, &taken);
+        Inflated { lines: self.provenance(modifications, taken, true), structure, objs }
     }
 
-    pub fn forward_inflate(&self, modifications: &StructDiff) -> Structure {
-        let remade = modifications.patch(self.contents.iter());
-        Self::inflate(&self.config, &remade, &modifications.added)
+    pub fn forward_inflate(&self, modifications: &StructDiff, inflation: &Inflation) -> Inflated {
+        self.inflate_patched(modifications, &modifications.added, inflation)
     }
 
-    // Todo: Make exclusive?
-    pub fn backward_inflate(&self, modifications: &StructDiff) -> Structure {
-        Self::inflate(&self.config, &self.contents, &modifications.removed)
+    pub fn backward_inflate(&self, modifications: &StructDiff, inflation: &Inflation) -> Inflated {
+        let (structure, taken) = Self::inflate(&self.config, &self.contents, &modifications.removed, inflation);
+        let objs = Self::shown_objs(&self.config, &self.contents, &taken);
+        Inflated { lines: self.provenance(modifications, taken, false), structure, objs }
     }
 
-    pub fn conflicts(&self, left: &StructDiff, right: &StructDiff) -> Option<(Structure, Structure)> {
-        let left = self.forward_inflate(left);
-        let right = self.forward_inflate(right);
- 
-        let overlapping = |((i, left), (j, right)): ((usize, Vec<String>), (usize, Vec<String>))| {
-            let mut same = left
+    //  A mod with the edits made to a forward inflated view of it carried over to the whole
+    //  map. Elided regions can't be edited, but lines can be added either side of them.
+    //  Lines added between two shown ones go right after the first of them.
+    pub fn edit_view(&self, modifications: &StructDiff, inflation: &Inflation, edited: &[String], comment: &str) -> Result<StructDiff, ViewError> {
+        let patched = modifications.patch_units(&self.contents);
+        let (view, taken) = Self::inflate(&self.config, &patched, &modifications.added, inflation);
+
+        let mut removed = vec![false; patched.len()];
+        let mut inserted = vec![vec![]; patched.len() + 1];
+        let mut at = 0;
+
+        for line in diff::slice(&view.contents, edited) {
+            match line {
+                DiffResult::Both(..) => at += 1,
+                DiffResult::Left(_) => {
+                    let unit = taken[at].ok_or(ViewError::Elided { line: at })?;
+                    removed[unit] = true;
+                    at += 1;
+                }
+                DiffResult::Right(line) => {
+                    let before = at.checked_sub(1).and_then(|i| taken[i]).map(|unit| unit + 1);
+                    let after = taken.get(at).copied().flatten();
+                    let unit = before.or(after).unwrap_or(match at {
+                        0 => 0,
+                        _ => patched.len(),
+                    });
+
+                    inserted[unit].push(line.clone());
+                }
+            }
+        }
+
+        let units = (0..=patched.len())
+            .flat_map(|unit| inserted[unit]
                 .iter()
-                .zip(right.iter())  //Todo: Handle size mismatch
-                .filter(|(a, b)| a == b)
-                .peekable();
+                .cloned()
+                .chain(patched.get(unit).filter(|_| !removed[unit]).cloned())
+            )
+            .collect::<Vec<_>>();
 
-            same.peek()
-                .is_some()
-                .then(|| (i, j))
-        };
+        Ok(modifications.rebuilt(&self.contents, &units, comment))
+    }
 
-        let mut collisions = left
-            .keys()
-            .into_iter()
-            .zip(right.keys().into_iter())
-            .filter_map(overlapping)
-            .peekable();
-
-        collisions.peek().is_some().then(|| {
-            let keep_left = Self::inflate(
-                &self.config, 
-                &left.contents, 
-                &collisions
-                    .clone()
-                    .map(|(i, _)| i)
-                    .collect::<Vec<_>>()
-            );
+    //  Units a mod leaves alone, out of the ones it changed
+    fn kept(len: usize, changed: &[usize]) -> Vec<usize> {
+        (0..len)
+            .filter(|i| changed.binary_search(i).is_err())
+            .collect()
+    }
 
-            let keep_right = Self::inflate(
-                &self.config,
-                &right.contents,
-                &collisions
-                    .map(|(_, j)| j)
-                    .collect::<Vec<_>>()
+    //  Keys of the objects a mod touches, whether it's the original or the patched version they
+    //  have, each with the lines the patched versions start at. An original object's patched
+    //  version is whatever ends up between the lines the mod kept either side of it.
+    fn changed(&self, modifications: &StructDiff) -> HashMap<Vec<String>, Vec<usize>> {
+        let patched = self.patched(modifications);
+        let original_kept = Self::kept(self.contents.len(), &modifications.removed);
+        let patched_kept = Self::kept(patched.contents.len(), &modifications.added);
+
+        let touching = |obj: &Obj, indices: &[usize]| indices
+            .get(indices.partition_point(|&i| i < obj.range.lower))
+            .is_some_and(|&i| i <= obj.range.upper);
+        let patched_objs = patched.index().objs;
+
+        let mut changed = HashMap::<_, Vec<_>>::new();
+        for obj in self.index().objs.into_iter().filter(|obj| touching(obj, &modifications.removed)) {
+            let before = original_kept.partition_point(|&i| i < obj.range.lower);
+            let after = original_kept.partition_point(|&i| i <= obj.range.upper);
+            let from = before.checked_sub(1).map_or(0, |before| patched_kept[before] + 1);
+            let to = patched_kept.get(after).copied().unwrap_or(patched.contents.len());
+
+            changed.entry(obj.key).or_default().extend(patched_objs
+                .iter()
+                .filter(|patched| from < to && patched.range.lower < to && from <= patched.range.upper)
+                .map(|patched| patched.range.lower)
             );
+        }
+
+        for obj in patched_objs.iter().filter(|obj| touching(obj, &modifications.added)) {
+            changed.entry(obj.key.clone()).or_default().push(obj.range.lower);
+        }
+
+        changed
+    }
+
+    //  Objects changed by both mods, as the lines they start at once patched by each.
+    //  Objects are matched by key rather than position, so mods inserting or moving
+    //  objects elsewhere don't shift what gets compared. Keys can match without being
+    //  equal (see Comparator), so every pair of changed objects is compared.
+    pub fn collisions(&self, left: &StructDiff, right: &StructDiff) -> Vec<(Vec<usize>, Vec<usize>)> {
+        let right_changed = self.changed(right);
+        self.changed(left)
+            .into_iter()
+            .flat_map(|(key, left_lines)| right_changed
+                .iter()
+                .filter(move |(other, _)| self.config.same(&key, other))
+                .map(move |(_, right_lines)| (left_lines.clone(), right_lines.clone()))
+            )
+            .collect()
+    }
 
-            (keep_left, keep_right)
+    //  Conflicts are whatever merge couldn't reconcile or resolve, see merge.rs
+    pub fn conflicts(&self, left: &StructDiff, right: &StructDiff, resolution: &Resolution, inflation: &Inflation) -> Option<Conflicts> {
+        let conflicts = self.merge(left, right, resolution).conflicts;
+
+        (!conflicts.is_empty()).then(|| {
+            //  Only what each side changed is shown, a conflict also covers what the other side
+            //  changed next to it, which this side might have left alone
+            let units = |modifications: &StructDiff, side: fn(&Conflict) -> Range<usize>| conflicts
+                .iter()
+                .flat_map(side)
+                .filter(|unit| modifications.added.binary_search(unit).is_ok())
+                .collect::<Vec<_>>();
+
+            Conflicts {
+                left: self.inflate_patched(left, &units(left, |conflict| conflict.left.clone()), inflation),
+                right: self.inflate_patched(right, &units(right, |conflict| conflict.right.clone()), inflation),
+                objects: conflicts
+            }
         })
     }
 }
@@ -191,7 +1146,7 @@ impl Structure {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use indoc::{indoc, formatdoc};
+    use indoc::indoc;
 
     const ORIGINAL: &str = indoc!(
         "config: {
@@ -247,7 +1202,7 @@ mod tests {
         objs: {
             hold: {
                 lane: 1,
-                time: 4,
+                time: 6,
                 color: 0x00FF00,
             },
         },"
@@ -272,7 +1227,7 @@ mod tests {
                     "close": "\\}"
                 },
                 "expander": {
-                    "prefix": "(press|hold): \\{",
+                    "prefix": "(?P<kind>press|hold): \\{",
                     "open": "\\{",
                     "close": "\\}"
                 }
@@ -286,18 +1241,32 @@ mod tests {
 
     #[test]
     fn key_find() {
-        assert_eq!(config().keys[0].find("        lane: 2,"), vec!["2"])
+        assert_eq!(config().keys[0].find("        lane: 2,", None), vec!["2"])
     }
 
-    fn map_keys() {
-        let map = Structure::new(ORIGINAL.to_string(), config());
-        let mut keys = map.keys();
-        keys.sort();
+    #[test]
+    fn map_index() {
+        let index = Structure::new(ORIGINAL.to_string(), config()).index();
+
+        let ranges = index.objs.iter().map(|obj| (obj.range.lower, obj.range.upper)).collect::<Vec<_>>();
+        let keys = index.objs.iter().map(|obj| obj.key.clone()).collect::<Vec<_>>();
+        assert_eq!(ranges, vec![(5, 9), (10, 14), (15, 19)]);
+        assert_eq!(keys, vec![vec!["0", "0"], vec!["1", "0"], vec!["2", "0"]]);
+        assert!(index.errors.is_empty());
+    }
+
+    #[test]
+    fn map_index_errors() {
+        let text = ORIGINAL
+            .replacen("time: 0,", "time: 0, time: 2,", 1)
+            .replacen("lane: 1,", "", 1);
 
-        let line_nums = keys.iter().map(|(i, _)| *i).collect::<Vec<_>>();
-        let keys = keys.iter().cloned().map(|(_, k)| k).collect::<Vec<_>>();
-        assert_eq!(line_nums, Vec::<usize>::from([6, 11, 16]));
-        assert_eq!(keys, vec![vec!["0"], vec!["1"], vec!["2"]]);
+        let index = Structure::new(text, config()).index();
+        assert_eq!(index.objs.len(), 1);
+        assert_eq!(index.errors, vec![
+            KeyError::Ambiguous { key: "time: [0-9]*".to_string(), line: 5, values: vec!["0".to_string(), "2".to_string()] },
+            KeyError::Missing { key: "lane: [0-9]*".to_string(), line: 10 },
+        ]);
     }
 
     #[test]
@@ -310,15 +1279,345 @@ mod tests {
     #[test]
     fn config_objs() {
         let map = Structure::new(ORIGINAL.to_string(), config());
-        let objs = map.config.objs(ORIGINAL.lines());
-        assert_eq!(objs, [(5, 9), (10, 14), (15, 19)].into());
+        let objs = map.config
+            .objs(ORIGINAL.lines())
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect::<Vec<_>>();
+
+        assert_eq!(RangeVec::from(objs), [(5, 9), (10, 14), (15, 19)].into());
+    }
+
+    #[test]
+    fn config_kinds() {
+        let kinds = config()
+            .objs(ORIGINAL.lines())
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .collect::<Vec<_>>();
+
+        assert_eq!(kinds, [Some("press"), Some("hold"), Some("press")].map(|kind| kind.map(String::from)));
+    }
+
+    #[test]
+    fn summary() {
+        let map = Structure::new(ORIGINAL.to_string(), config());
+        let modifications = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00FFFF"), "");
+
+        assert_eq!(map.summary(&modifications), vec![
+            "removes hold object at lines 11-15",
+            "adds hold object at lines 11-15",
+        ]);
+    }
+
+    #[test]
+    fn breadcrumbs() {
+        let map = Structure::new(ORIGINAL.to_string(), config());
+        let added = ORIGINAL
+            .replace("0x00FF00", "0x00FFFF")
+            .replace("    },\n}", "    },\n    press: { lane: 3, time: 2 },\n}");
+
+        let modifications = StructDiff::build_from(ORIGINAL, &added, "");
+        assert_eq!(map.breadcrumbs(&modifications), vec![
+            Breadcrumb { path: "objs > hold[lane=1,time=0]".to_string(), lines: 10..15 },
+            Breadcrumb { path: "objs > press[lane=3,time=2]".to_string(), lines: 20..20 },
+        ]);
+    }
+
+    const NESTED: &str = indoc!(
+        "config: {
+            scroll_speed: 0.1,
+        },
+        tracks: {
+            track: {
+                name: \"drums\",
+                note: {
+                    lane: 0,
+                    time: 0,
+                },
+                note: {
+                    lane: 1,
+                    time: 4,
+                },
+            },
+            track: {
+                name: \"bass\",
+                note: {
+                    lane: 0,
+                    time: 8,
+                },
+            },
+        }"
+    );
+
+    fn nested_config() -> Config {
+        let config_json = indoc!(r#"
+            {
+                "keys": [],
+                "filter": {
+                    "prefix": "tracks:",
+                    "open": "\\{",
+                    "close": "\\}"
+                },
+                "expander": {
+                    "prefix": "track: \\{",
+                    "open": "\\{",
+                    "close": "\\}"
+                },
+                "nested": [
+                    {
+                        "name": "note",
+                        "divider": {
+                            "prefix": "note: \\{",
+                            "open": "\\{",
+                            "close": "\\}"
+                        }
+                    }
+                ]
+            }
+        "#);
+
+        serde_json::from_str::<ConfigDef>(config_json)
+            .unwrap()
+            .into()
+    }
+
+    #[test]
+    fn config_tree() {
+        let config = nested_config();
+        let tree = config.tree(NESTED.lines());
+
+        let shape = tree
+            .iter()
+            .map(|track| (
+                (track.range.lower, track.range.upper),
+                track.children
+                    .iter()
+                    .map(|note| (note.kind.as_deref(), (note.range.lower, note.range.upper)))
+                    .collect::<Vec<_>>()
+            ))
+            .collect::<Vec<_>>();
+
+        assert_eq!(shape, vec![
+            ((4, 14), vec![(Some("note"), (6, 9)), (Some("note"), (10, 13))]),
+            ((15, 21), vec![(Some("note"), (17, 20))]),
+        ]);
+
+        let objs = config
+            .objs(NESTED.lines())
+            .into_iter()
+            .map(|(range, kind)| (kind, (range.lower, range.upper)))
+            .collect::<Vec<_>>();
+
+        let note = Some("note".to_string());
+        assert_eq!(objs, vec![(note.clone(), (6, 9)), (note.clone(), (10, 13)), (note, (17, 20))]);
+    }
+
+    #[test]
+    fn config_trails() {
+        let trails = nested_config()
+            .trails(NESTED.lines())
+            .into_iter()
+            .map(|(range, trail)| (range.lower, trail.join(" > ")))
+            .collect::<Vec<_>>();
+
+        assert_eq!(trails, vec![
+            (6, "tracks > track > note".to_string()),
+            (10, "tracks > track > note".to_string()),
+            (17, "tracks > track > note".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn forward_inflate_nested() {
+        let modded = NESTED.replace("time: 4", "time: 5");
+        let inflated = Structure::new(NESTED.to_string(), nested_config())
+            .forward_inflate(&StructDiff::build_from(NESTED, &modded, ""), &Inflation::default())
+            .structure.contents;
+
+        let expected_inflated = indoc!(
+            "config: {
+                scroll_speed: 0.1,
+            },
+            tracks: {
+                track: {
+                    name: \"drums\",
+                    note: {
+                        lane: 1,
+                        time: 5,
+                    },
+                },
+            }"
+        );
+
+        assert_eq!(inflated.join("\n"), expected_inflated);
+    }
+
+    #[test]
+    fn conflicts_spans() {
+        let original = r#"{"objs":[{"lane":0,"time":0},{"lane":1,"time":0},{"lane":2,"time":0}]}"#;
+        let modded_a = original.replace(r#""lane":1,"time":0"#, r#""lane":1,"time":4"#);
+        let modded_b = original.replace(r#""lane":2,"time":0"#, r#""lane":2,"time":8"#);
+        let modded_c = original.replace(r#""lane":1,"time":0"#, r#""lane":1,"time":2"#);
+
+        let config_json = indoc!(r#"
+            {
+                "keys": [
+                    {
+                        "fuzzed": "\"lane\":[0-9]*",
+                        "strict": "[0-9]+"
+                    }
+                ],
+                "filter": {
+                    "prefix": "\"objs\":",
+                    "open": "\\[",
+                    "close": "\\]"
+                },
+                "expander": {
+                    "prefix": "\\{\"lane\"",
+                    "open": "\\{",
+                    "close": "\\}"
+                }
+            }
+        "#);
+
+        let config: Config = serde_json::from_str::<ConfigDef>(config_json).unwrap().into();
+        let modded_a = StructDiff::build_from_spans(original, &modded_a, &config, "");
+        let modded_b = StructDiff::build_from_spans(original, &modded_b, &config, "");
+        let modded_c = StructDiff::build_from_spans(original, &modded_c, &config, "");
+
+        let map = Structure {
+            contents: modded_a.units(original),
+            config
+        };
+
+        assert_eq!(
+            modded_a.join(&map.forward_inflate(&modded_a, &Inflation::default()).structure.contents),
+            r#"{"objs":[{"lane":1,"time":4}]}"#
+        );
+        assert!(map.conflicts(&modded_a, &modded_b, &Resolution::default(), &Inflation::default()).is_none());
+        assert!(map.conflicts(&modded_a, &modded_c, &Resolution::default(), &Inflation::default()).is_some());
+    }
+
+    #[test]
+    fn conflicts_by_key() {
+        let map = Structure::new(ORIGINAL.to_string(), config());
+        let inserted = ORIGINAL.replacen("objs: {", "objs: {\n    press: {\n        lane: 3,\n        time: 8,\n    },", 1);
+
+        //  Both retime the same hold, so its key differs afterwards but not before
+        let retimed_a = StructDiff::build_from(ORIGINAL, &ORIGINAL.replacen("time: 0,\n        color: 0x00FF00", "time: 4,\n        color: 0x00FF00", 1), "");
+        let retimed_b = StructDiff::build_from(ORIGINAL, &inserted.replacen("time: 0,\n        color: 0x00FF00", "time: 2,\n        color: 0x00FF00", 1), "");
+        let recolored = StructDiff::build_from(ORIGINAL, &inserted.replace("0x0000FF", "0x0000AA"), "");
+
+        let Conflicts { left, right, .. } = map.conflicts(&retimed_a, &retimed_b, &Resolution::default(), &Inflation::default()).unwrap();
+        assert!(left.structure.contents.contains(&"        time: 4,".to_string()));
+        assert!(right.structure.contents.contains(&"        time: 2,".to_string()));
+        assert!(!right.structure.contents.contains(&"        lane: 3,".to_string()));
+
+        assert!(map.conflicts(&retimed_a, &recolored, &Resolution::default(), &Inflation::default()).is_none());
+    }
+
+    #[test]
+    fn comparators() {
+        assert!(Comparator::CaseInsensitive.same("Red", "rED"));
+        assert!(Comparator::Tolerance(2.0).same("1000", "1002"));
+        assert!(!Comparator::Tolerance(2.0).same("1000", "1003"));
+        assert!(Comparator::Tolerance(2.0).same("red", "red"));
+        assert!(Comparator::Overlap.same("1000-1500", "1400-1600"));
+        assert!(Comparator::Overlap.same("1000-1500", "1200"));
+        assert!(!Comparator::Overlap.same("-5 to -3", "-2"));
+    }
+
+    #[test]
+    fn region_comparator() {
+        assert!(Comparator::Region.same("0 0 10 10", "5 5 15 15"));
+        assert!(!Comparator::Region.same("0 0 10 10", "10 0 20 10"));
+        assert!(Comparator::Region.same("0 0 10 10", "10 5 10 5"));
+        assert!(!Comparator::Region.same("0 0 10 10", "11 5 11 5"));
+    }
+
+    const ENTITIES: &str = indoc!(
+        "[entities]
+        kind, x, y, w, h
+        crate, 0, 0, 10, 10
+        spike, 40, 0, 10, 5
+        door, 80, 0, 10, 20"
+    );
+
+    #[test]
+    fn conflicts_spatial() {
+        let config: Config = serde_json::from_str::<ConfigDef>(
+            r#"{ "keys": [{ "x": { "column": "x" }, "y": { "column": "y" }, "w": { "column": "w" }, "h": { "column": "h" } }], "expander": { "table": "\\[entities\\]" } }"#
+        )
+        .unwrap()
+        .into();
+
+        let map = Structure::new(ENTITIES.to_string(), config);
+        assert_eq!(map.index().objs[1].key, vec!["40 0 50 5"]);
+
+        let moved = StructDiff::build_from(ENTITIES, &ENTITIES.replace("spike, 40, 0", "spike, 45, 0"), "");
+        let overlapping = StructDiff::build_from(ENTITIES, &format!("{ENTITIES}\ncoin, 48, 2, 1, 1"), "");
+        let apart = StructDiff::build_from(ENTITIES, &format!("{ENTITIES}\ncoin, 60, 2, 1, 1"), "");
+
+        assert!(map.conflicts(&moved, &overlapping, &Resolution::default(), &Inflation::default()).is_some());
+        assert!(map.conflicts(&moved, &apart, &Resolution::default(), &Inflation::default()).is_none());
+    }
+
+    #[test]
+    fn conflicts_tolerance() {
+        let config: Config = serde_json::from_str::<ConfigDef>(
+            r#"{ "keys": [{ "column": "lane" }, { "column": "time", "compare": { "tolerance": 2 } }], "expander": { "table": "\\[notes\\]" } }"#
+        )
+        .unwrap()
+        .into();
+
+        let original = TABLE.replace("2, 0, blue", "2, 1000, blue");
+        let map = Structure::new(original.clone(), config);
+        let modded_a = StructDiff::build_from(&original, &original.replace("2, 1000, blue", "2, 1000, navy"), "");
+        let modded_b = StructDiff::build_from(&original, &original.replace("2, 1000, blue", "2, 1000, blue\n2, 1002, red"), "");
+        let modded_c = StructDiff::build_from(&original, &format!("{original}\n2, 1003, red"), "");
+
+        assert!(map.conflicts(&modded_a, &modded_b, &Resolution::default(), &Inflation::default()).is_some());
+        assert!(map.conflicts(&modded_a, &modded_c, &Resolution::default(), &Inflation::default()).is_none());
+    }
+
+    const TABLE: &str = indoc!(
+        "[config]
+        speed = 1
+
+        [notes]
+        lane, time, color
+        0, 0, red
+        1, 0, green
+        2, 0, blue"
+    );
+
+    #[test]
+    fn conflicts_columns() {
+        let config: Config = serde_json::from_str::<ConfigDef>(
+            r#"{ "keys": [{ "column": "lane" }], "expander": { "table": "\\[notes\\]" } }"#
+        )
+        .unwrap()
+        .into();
+
+        let map = Structure::new(TABLE.to_string(), config);
+        let modded_a = StructDiff::build_from(TABLE, &TABLE.replace("1, 0, green", "1, 0, lime"), "");
+        let modded_b = StructDiff::build_from(TABLE, &TABLE.replace("1, 0, green", "1, 2, green"), "");
+        let modded_c = StructDiff::build_from(TABLE, &TABLE.replace("2, 0, blue", "2, 0, navy"), "");
+
+        assert_eq!(
+            map.forward_inflate(&modded_a, &Inflation::default()).structure.contents,
+            vec!["[notes]", "lane, time, color", "1, 0, lime"]
+        );
+        assert!(map.conflicts(&modded_a, &modded_b, &Resolution::default(), &Inflation::default()).is_some());
+        assert!(map.conflicts(&modded_a, &modded_c, &Resolution::default(), &Inflation::default()).is_none());
     }
 
     #[test]
     fn forward_inflate() {
         let inflated = Structure::new(ORIGINAL.to_string(), config())
-            .forward_inflate(&StructDiff::build_from(ORIGINAL, MODDED_A, ""))
-            .contents;
+            .forward_inflate(&StructDiff::build_from(ORIGINAL, MODDED_A, ""), &Inflation::default())
+            .structure.contents;
 
         let expected_inflated = {
             let text = indoc!(
@@ -349,8 +1648,8 @@ mod tests {
     #[test]
     fn backward_inflate() {
         let inflated = Structure::new(ORIGINAL.to_string(), config())
-            .backward_inflate(&StructDiff::build_from(ORIGINAL, MODDED_A, ""))
-            .contents;
+            .backward_inflate(&StructDiff::build_from(ORIGINAL, MODDED_A, ""), &Inflation::default())
+            .structure.contents;
 
         let expected_inflated = {
             let text = indoc!(
@@ -380,13 +1679,110 @@ mod tests {
         assert_eq!(inflated, expected_inflated);
     }
 
+    #[test]
+    fn inflation_options() {
+        let map = Structure::new(ORIGINAL.to_string(), config());
+        let modifications = StructDiff::build_from(ORIGINAL, &ORIGINAL.replace("0x00FF00", "0x00FFFF"), "");
+        let lines = ORIGINAL.lines().map(String::from).collect::<Vec<_>>();
+        let elided = |shown: &[String]| [vec!["...".to_string()], shown.to_vec(), vec!["...".to_string()]].concat();
+
+        let exclusive = Inflation { exclusive: true, outside: false, elision: Some("...".to_string()), ..Inflation::default() };
+        assert_eq!(map.backward_inflate(&modifications, &exclusive).structure.contents, elided(&lines[13..14]));
+
+        let neighbours = Inflation { context: 1, outside: false, elision: Some("...".to_string()), ..Inflation::default() };
+        let mut patched = lines.clone();
+        patched[13] = patched[13].replace("0x00FF00", "0x00FFFF");
+        assert_eq!(map.forward_inflate(&modifications, &neighbours).structure.contents, elided(&patched[5..20]));
+
+        let inclusive = Inflation { elision: Some("...".to_string()), ..Inflation::default() };
+        let inflated = map.backward_inflate(&modifications, &inclusive).structure.contents;
+        assert_eq!(inflated[5..8], ["...".to_string(), lines[10].clone(), lines[11].clone()]);
+    }
+
+    #[test]
+    fn inflate_past_trailing_newline() {
+        //  The mod's only added line is the empty one after the trailing newline, which
+        //  isn't a unit of the patched text, so there's nothing to show
+        let original = "head\nobjs: {\n\n}\ntail";
+        let modifications = StructDiff::build_from(original, "head\nobjs: {\n", "");
+        let exclusive = Inflation { exclusive: true, ..Inflation::default() };
+
+        let inflated = Structure::new(original.to_string(), config()).forward_inflate(&modifications, &exclusive);
+        assert!(inflated.structure.contents.is_empty());
+    }
+
+    #[test]
+    fn inflated_lines() {
+        let map = Structure::new(ORIGINAL.to_string(), config());
+        let modded = ORIGINAL
+            .replace("0x00FF00", "0x00FFFF")
+            .replace("objs: {\n", "objs: {\n    press: { lane: 3, time: 2 },\n");
+
+        let modifications = StructDiff::build_from(ORIGINAL, &modded, "");
+        let inflation = Inflation { outside: false, elision: Some("...".to_string()), ..Inflation::default() };
+        let kept = |original, patched| Provenance::Kept { original, patched };
+
+        assert_eq!(map.forward_inflate(&modifications, &inflation).lines, vec![
+            Provenance::Inserted,
+            Provenance::Added(5),
+            Provenance::Inserted,
+            kept(10, 11),
+            kept(11, 12),
+            kept(12, 13),
+            Provenance::Added(14),
+            kept(14, 15),
+            Provenance::Inserted,
+        ]);
+
+        assert_eq!(map.backward_inflate(&modifications, &inflation).lines, vec![
+            Provenance::Inserted,
+            kept(10, 11),
+            kept(11, 12),
+            kept(12, 13),
+            Provenance::Removed(13),
+            kept(14, 15),
+            Provenance::Inserted,
+        ]);
+
+        let inflated = map.forward_inflate(&modifications, &inflation);
+        assert_eq!(inflated.objs, vec![
+            (InclRange::new(1, 1), Some("press".to_string())),
+            (InclRange::new(3, 7), Some("hold".to_string())),
+        ]);
+        assert_eq!(inflated.describe(), ["press object at line 2", "hold object at lines 4-8"]);
+    }
+
+    #[test]
+    fn edit_view() {
+        let map = Structure::new(ORIGINAL.to_string(), config());
+        let recolored = ORIGINAL.replace("0x00FF00", "0x00FFFF");
+        let modifications = StructDiff::build_from(ORIGINAL, &recolored, "");
+        let inflation = Inflation { outside: false, elision: Some("...".to_string()), ..Inflation::default() };
+        let view = map.forward_inflate(&modifications, &inflation).structure.contents;
+
+        let mut edited = view.clone();
+        edited[3] = edited[3].replace("time: 0", "time: 3");
+        edited.insert(1, "    press: { lane: 3, time: 2 },".to_string());
+
+        let expected = recolored
+            .replace("time: 0,\n        color: 0x00FFFF", "time: 3,\n        color: 0x00FFFF")
+            .replace("    hold: {", "    press: { lane: 3, time: 2 },\n    hold: {");
+
+        let amended = map.edit_view(&modifications, &inflation, &edited, "").unwrap();
+        assert_eq!(amended.patch_units(&map.contents).join("\n"), expected);
+
+        let mut elided = view.clone();
+        elided[0] = "    press: { lane: 3, time: 2 },".to_string();
+        assert_eq!(map.edit_view(&modifications, &inflation, &elided, "").unwrap_err(), ViewError::Elided { line: 0 });
+    }
+
     #[test]
     fn conflicts() {
         let map = Structure::new(ORIGINAL.to_string(), config());
         let modded_a = StructDiff::build_from(ORIGINAL, MODDED_A, "");
         let modded_b = StructDiff::build_from(ORIGINAL, MODDED_B, "");
-        let conflicts = map.conflicts(&modded_a, &modded_b);
-        assert!(matches!(conflicts, Some(_)));
+        let conflicts = map.conflicts(&modded_a, &modded_b, &Resolution::default(), &Inflation::default());
+        assert!(conflicts.is_some());
 
         let a_conflicts = {
             let text = indoc!(
@@ -421,7 +1817,7 @@ mod tests {
                 objs: {
                     hold: {
                         lane: 1,
-                        time: 4,
+                        time: 6,
                         color: 0x00FF00,
                     },
                 },"
@@ -434,7 +1830,11 @@ mod tests {
 
         let conflicts = conflicts.unwrap();
 
-        assert_eq!(conflicts.0.contents, a_conflicts);
-        assert_eq!(conflicts.1.contents, b_conflicts);
+        assert_eq!(conflicts.left.structure.contents, a_conflicts);
+        assert_eq!(conflicts.right.structure.contents, b_conflicts);
+
+        let hold = conflicts.objects.iter().find(|conflict| conflict.kinds == ["hold"]).unwrap();
+        assert!(hold.keys.contains(&vec!["1".to_string(), "0".to_string()]));
+        assert!(hold.left_text.contains("time: 4") && hold.right_text.contains("time: 6"));
     }
 }
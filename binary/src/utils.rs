@@ -1,6 +1,7 @@
 use std::{
     io::prelude::*,
     fs::{read_to_string, remove_file, create_dir_all, File},
+    ops::Range,
     path::{Path, PathBuf}
 };
 
@@ -13,20 +14,6 @@ use crate::struct_diff::*;
 
 pub const IO_SEPARATOR: &str = "OMFG_IO_SEPARATOR";
 
-pub trait DebugPrint {
-    fn dprint(self, ident: &str) -> Self;
-}
-
-impl<T> DebugPrint for T
-where
-    T: std::fmt::Debug + Clone + Send + Sync
-{
-    fn dprint(self, ident: &str) -> Self {
-        println!("{} {:?}", ident, self);
-        self
-    }
-}
-
 pub trait PathBufExt {
     fn read(&self) -> AnyHow<String>;
     fn write_plus(&self, content: &str) -> AnyHow<()>;
@@ -66,6 +53,9 @@ pub enum DividerDef {
         prefix: String,
         open: String,
         close: String,
+        quote: Option<String>,
+        escape: Option<String>,
+        comment: Option<String>,
     },
     Headings {
         fuzzed: String,
@@ -75,19 +65,59 @@ pub enum DividerDef {
     Enclosures {
         top: String,
         bottom: String,
+        quote: Option<String>,
+        escape: Option<String>,
+        comment: Option<String>,
+    },
+    Json {
+        json: String,
+    },
+    Xml {
+        xml: String,
+    },
+    Indented {
+        block: String,
+        level: Option<usize>,
+    },
+    Table {
+        table: String,
+        separator: Option<String>,
+        header: Option<bool>,
+    },
+    External {
+        command: String,
+        #[serde(default)]
+        args: Vec<String>,
     },
 }
 
 impl DividerDef {
+    fn try_into_ignore(quote: Option<String>, escape: Option<String>, comment: Option<String>) -> Option<Ignore> {
+        let regex = |pattern: Option<String>| pattern.map(|s| Regex::new(&s)).transpose();
+
+        match (regex(quote), regex(escape), regex(comment)) {
+            (Ok(quote), Ok(escape), Ok(comment)) => Some(
+                Ignore {
+                    quote,
+                    escape,
+                    comment
+                }
+            ),
+            _ => None
+        }
+    }
+
     pub fn try_into_divider(self) -> Option<Divider> {
         match self {
-            Self::Delimited { prefix, open, close } => {
-                match (Regex::new(&prefix), Regex::new(&open), Regex::new(&close)) {
-                    (Ok(prefix), Ok(open), Ok(close)) => Some(
+            Self::Delimited { prefix, open, close, quote, escape, comment } => {
+                let ignore = Self::try_into_ignore(quote, escape, comment);
+                match (Regex::new(&prefix), Regex::new(&open), Regex::new(&close), ignore) {
+                    (Ok(prefix), Ok(open), Ok(close), Some(ignore)) => Some(
                         Divider::Delimited {
                             prefix,
                             open,
-                            close
+                            close,
+                            ignore
                         }
                     ),
                     _ => None
@@ -105,17 +135,32 @@ impl DividerDef {
                     _ => None
                 }
             },
-            Self::Enclosures { top, bottom } => {
-                match (Regex::new(&top), Regex::new(&bottom)) {
-                    (Ok(top), Ok(bottom)) => Some(
+            Self::Enclosures { top, bottom, quote, escape, comment } => {
+                let ignore = Self::try_into_ignore(quote, escape, comment);
+                match (Regex::new(&top), Regex::new(&bottom), ignore) {
+                    (Ok(top), Ok(bottom), Some(ignore)) => Some(
                         Divider::Enclosures {
                             top,
-                            bottom
+                            bottom,
+                            ignore
                         }
                     ),
                     _ => None
                 }
             },
+            Self::Json { json } => Selector::parse(&json).map(|selector| Divider::Json { selector }),
+            Self::Xml { xml } => XmlPath::parse(&xml).map(|path| Divider::Xml { path }),
+            Self::Indented { block, level } => Regex::new(&block)
+                .ok()
+                .map(|block| Divider::Indented { block, level }),
+            Self::Table { table, separator, header } => Regex::new(&table)
+                .ok()
+                .map(|start| Divider::Table {
+                    start,
+                    separator: separator.unwrap_or_else(|| ",".to_string()),
+                    header: header.unwrap_or(true)
+                }),
+            Self::External { command, args } => Some(Divider::External { command, args, outputs: Outputs::default() }),
         }
     }
 }
@@ -147,12 +192,95 @@ impl From<Change<String>> for ChangeString {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum KeepDef {
+    #[serde(rename = "keep-left")]
+    Left,
+    #[serde(rename = "keep-right")]
+    Right,
+    #[serde(rename = "keep-both")]
+    Both,
+}
+
+impl From<KeepDef> for Keep {
+    fn from(keep_def: KeepDef) -> Self {
+        match keep_def {
+            KeepDef::Left => Keep::Left,
+            KeepDef::Right => Keep::Right,
+            KeepDef::Both => Keep::Both,
+        }
+    }
+}
+
+impl From<Keep> for KeepDef {
+    fn from(keep: Keep) -> Self {
+        match keep {
+            Keep::Left => KeepDef::Left,
+            Keep::Right => KeepDef::Right,
+            Keep::Both => KeepDef::Both,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RuleDef {
+    key: String,
+    matches: String,
+    keep: KeepDef,
+}
+
+//  Either "keep-left", "keep-right", "keep-both" or
+//  { "rules": [{ "key": "time", "matches": "^0$", "keep": "keep-right" }], "otherwise": "keep-left" }
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ResolutionDef {
+    Keep(KeepDef),
+    Rules {
+        rules: Vec<RuleDef>,
+        otherwise: Option<KeepDef>,
+    },
+}
+
+//  Fails on a rule that doesn't match with a valid regex
+impl TryFrom<ResolutionDef> for Resolution {
+    type Error = regex::Error;
+
+    fn try_from(resolution_def: ResolutionDef) -> Result<Self, Self::Error> {
+        match resolution_def {
+            ResolutionDef::Keep(keep) => Ok(Resolution::keep(keep.into())),
+            ResolutionDef::Rules { rules, otherwise } => Ok(Resolution {
+                rules: rules
+                    .into_iter()
+                    .map(|rule| Ok(Rule {
+                        key: rule.key,
+                        matches: Regex::new(&rule.matches)?,
+                        keep: rule.keep.into(),
+                    }))
+                    .collect::<Result<_, Self::Error>>()?,
+                otherwise: otherwise.map(Keep::from),
+            }),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ResolvedDef {
+    keys: Vec<Vec<String>>,
+    kept: KeepDef,
+    mod_id: String,
+    comment: String,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct StructDiffDef {
     comment: String,
     changes: Vec<ChangeString>,
     removed: Vec<usize>,
-    added: Vec<usize>
+    added: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spans: Option<Vec<usize>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resolved: Vec<ResolvedDef>,
 }
 
 impl From<StructDiffDef> for StructDiff {
@@ -161,7 +289,12 @@ impl From<StructDiffDef> for StructDiff {
             comment: mod_def.comment,
             removed: mod_def.removed,
             added: mod_def.added,
-            changes: mod_def.changes.into_iter().map(|c| c.into()).collect()
+            spans: mod_def.spans,
+            changes: mod_def.changes.into_iter().map(|c| c.into()).collect(),
+            resolved: mod_def.resolved
+                .into_iter()
+                .map(|resolved| Resolved { keys: resolved.keys, kept: resolved.kept.into(), mod_id: resolved.mod_id, comment: resolved.comment })
+                .collect()
         }
     }
 }
@@ -172,34 +305,236 @@ impl From<StructDiff> for StructDiffDef {
             comment: mod_def.comment,
             removed: mod_def.removed,
             added: mod_def.added,
-            changes: mod_def.changes.into_iter().map(|c| c.into()).collect()
+            spans: mod_def.spans,
+            changes: mod_def.changes.into_iter().map(|c| c.into()).collect(),
+            resolved: mod_def.resolved
+                .into_iter()
+                .map(|resolved| ResolvedDef { keys: resolved.keys, kept: resolved.kept.into(), mod_id: resolved.mod_id, comment: resolved.comment })
+                .collect()
         }
     }
 }
 
+//  Ranges are of units, end exclusive, in the original and each patched side
+#[derive(Serialize)]
+pub struct ConflictDef {
+    kinds: Vec<String>,
+    keys: Vec<Vec<String>>,
+    original: Range<usize>,
+    left: Range<usize>,
+    right: Range<usize>,
+    left_text: String,
+    right_text: String,
+}
+
+impl From<Conflict> for ConflictDef {
+    fn from(conflict: Conflict) -> Self {
+        ConflictDef {
+            kinds: conflict.kinds,
+            keys: conflict.keys,
+            original: conflict.original,
+            left: conflict.left,
+            right: conflict.right,
+            left_text: conflict.left_text,
+            right_text: conflict.right_text,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReportDef {
+    conflicts: Vec<ConflictDef>,
+}
+
+impl From<Vec<Conflict>> for ReportDef {
+    fn from(conflicts: Vec<Conflict>) -> Self {
+        ReportDef { conflicts: conflicts.into_iter().map(ConflictDef::from).collect() }
+    }
+}
+
+//  "inserted", { "added": 4 }, { "removed": 3 } or { "kept": { "original": 3, "patched": 4 } }
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProvenanceDef {
+    Kept { original: usize, patched: usize },
+    Removed(usize),
+    Added(usize),
+    Inserted,
+}
+
+impl From<Provenance> for ProvenanceDef {
+    fn from(provenance: Provenance) -> Self {
+        match provenance {
+            Provenance::Kept { original, patched } => ProvenanceDef::Kept { original, patched },
+            Provenance::Removed(i) => ProvenanceDef::Removed(i),
+            Provenance::Added(i) => ProvenanceDef::Added(i),
+            Provenance::Inserted => ProvenanceDef::Inserted,
+        }
+    }
+}
+
+//  Lines are of the original, end exclusive
+#[derive(Serialize)]
+pub struct BreadcrumbDef {
+    path: String,
+    lines: Range<usize>,
+}
+
+impl From<Breadcrumb> for BreadcrumbDef {
+    fn from(breadcrumb: Breadcrumb) -> Self {
+        BreadcrumbDef { path: breadcrumb.path, lines: breadcrumb.lines }
+    }
+}
+
+//  "exact", "case-insensitive", "overlap" or { "tolerance": 2 }
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ComparatorDef {
+    #[default]
+    Exact,
+    CaseInsensitive,
+    Overlap,
+    Tolerance(f64),
+}
+
+impl From<ComparatorDef> for Comparator {
+    fn from(comparator_def: ComparatorDef) -> Self {
+        match comparator_def {
+            ComparatorDef::Exact => Comparator::Exact,
+            ComparatorDef::CaseInsensitive => Comparator::CaseInsensitive,
+            ComparatorDef::Overlap => Comparator::Overlap,
+            ComparatorDef::Tolerance(tolerance) => Comparator::Tolerance(tolerance),
+        }
+    }
+}
+
+//  Spatial keys take w and h together, or a radius, or neither for points
 #[derive(Serialize, Deserialize)]
-pub struct KeyDef {
-    fuzzed: Option<String>,
-    strict: String,
+#[serde(untagged)]
+pub enum KeyDef {
+    Spatial {
+        x: Box<KeyDef>,
+        y: Box<KeyDef>,
+        w: Option<Box<KeyDef>>,
+        h: Option<Box<KeyDef>>,
+        radius: Option<Box<KeyDef>>,
+    },
+    Pattern {
+        fuzzed: Option<String>,
+        strict: String,
+        #[serde(default)]
+        compare: ComparatorDef,
+    },
+    Column {
+        column: String,
+        #[serde(default)]
+        compare: ComparatorDef,
+    },
 }
 
 impl KeyDef {
     fn try_into_key(self) -> Option<Key> {
-        match (self.fuzzed.map(|s| Regex::new(&s)).transpose(), Regex::new(&self.strict)) {
-            (Ok(fuzzed), Ok(strict)) => Some(Key {
-                fuzzed,
-                strict
+        match self {
+            Self::Pattern { fuzzed, strict, compare } => {
+                match (fuzzed.map(|s| Regex::new(&s)).transpose(), Regex::new(&strict)) {
+                    (Ok(fuzzed), Ok(strict)) => Some(Key::Pattern {
+                        fuzzed,
+                        strict,
+                        compare: compare.into()
+                    }),
+                    _ => None
+                }
+            },
+            Self::Column { column, compare } => Some(Key::Column { name: column, compare: compare.into() }),
+            Self::Spatial { x, y, w, h, radius } => {
+                let key = |def: Box<KeyDef>| def.try_into_key().map(Box::new);
+                let extent = match (w, h, radius) {
+                    (None, None, None) => Extent::Point,
+                    (Some(w), Some(h), None) => Extent::Size { w: key(w)?, h: key(h)? },
+                    (None, None, Some(radius)) => Extent::Radius(key(radius)?),
+                    _ => return None,
+                };
+
+                Some(Key::Spatial { x: key(x)?, y: key(y)?, extent })
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NestedDef {
+    name: String,
+    divider: DividerDef,
+    #[serde(default)]
+    nested: Vec<NestedDef>,
+}
+
+impl NestedDef {
+    fn try_into_nested(self) -> Option<Nested> {
+        let nested = self.nested
+            .into_iter()
+            .map(|nested| nested.try_into_nested())
+            .collect::<Option<Vec<_>>>();
+
+        match (self.divider.try_into_divider(), nested) {
+            (Some(divider), Some(nested)) => Some(Nested {
+                name: self.name,
+                divider,
+                nested
             }),
             _ => None
         }
     }
 }
 
+//  e.g. { "exclusive": true, "context": 1, "outside": false, "elision": "..." },
+//  anything left out is the same as for Inflation::default
+#[derive(Serialize, Deserialize)]
+pub struct InflationDef {
+    #[serde(default)]
+    exclusive: bool,
+    #[serde(default)]
+    context: usize,
+    outside: Option<bool>,
+    elision: Option<String>,
+}
+
+impl From<InflationDef> for Inflation {
+    fn from(inflation_def: InflationDef) -> Self {
+        Self {
+            exclusive: inflation_def.exclusive,
+            context: inflation_def.context,
+            outside: inflation_def.outside.unwrap_or(true),
+            elision: inflation_def.elision,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MarkersDef {
+    start: String,
+    middle: String,
+    end: String,
+}
+
+impl From<MarkersDef> for Markers {
+    fn from(markers_def: MarkersDef) -> Self {
+        Self {
+            start: markers_def.start,
+            middle: markers_def.middle,
+            end: markers_def.end,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ConfigDef {
     filter: Option<DividerDef>,
     expander: Option<DividerDef>,
+    #[serde(default)]
+    nested: Vec<NestedDef>,
     keys: Vec<KeyDef>,
+    markers: Option<MarkersDef>,
 }
 
 impl From<ConfigDef> for Config {
@@ -216,6 +551,12 @@ impl From<ConfigDef> for Config {
                 .expect("Invalid expander")
         );
 
+        let nested = config_def.nested.into_iter().map(
+            |nested| nested
+                .try_into_nested()
+                .expect("Invalid nested object")
+        );
+
         let keys = config_def.keys.into_iter().map(
             |keydef| keydef
                 .try_into_key()
@@ -225,7 +566,9 @@ impl From<ConfigDef> for Config {
         Self {
             filter,
             expander,
-            keys: keys.collect()
+            nested: nested.collect(),
+            keys: keys.collect(),
+            markers: config_def.markers.map_or_else(Markers::default, Markers::from)
         }
     }
 }